@@ -0,0 +1,256 @@
+use crate::error::DocxError;
+use crate::image::{DocxImage, EMU};
+use crate::size::Length;
+use image::{GrayImage, ImageFormat, Luma};
+use qrcode::{Color, QrCode};
+use std::io::Cursor;
+
+// 生成图片的目标DPI，保证打印清晰
+static BARCODE_DPI: f64 = 300.0;
+// 二维码四周的空白（模块数）
+static QR_QUIET_ZONE: usize = 4;
+// 条形码左右两侧的空白（模块数）
+static BAR_QUIET_ZONE: usize = 10;
+// Code128字符的条空宽度，0-102为数据和切换字符，103-105为起始字符，106为终止字符
+static CODE128_PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+// Code128字符集B、C的起始字符和切换字符
+static CODE128_START_B: usize = 104;
+static CODE128_START_C: usize = 105;
+static CODE128_TO_B: usize = 100;
+static CODE128_TO_C: usize = 99;
+static CODE128_STOP: usize = 106;
+// EAN-13左侧奇校验（L）编码，右侧（R）为其取反，偶校验（G）为R的倒序
+static EAN_L_CODES: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011",
+    "0110111", "0001011",
+];
+// EAN-13第一位数字决定左侧6位数字的奇偶校验方式
+static EAN_PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL",
+    "LGGLGL",
+];
+
+/// 本地生成的二维码和条形码
+#[derive(Debug, Clone, PartialEq)]
+pub enum Barcode {
+    // 二维码，例如验证网址
+    QrCode(String),
+    // Code128条形码，支持ASCII可打印字符
+    Code128(String),
+    // EAN-13商品条码，12位数字时自动计算校验位
+    Ean13(String),
+}
+
+impl Barcode {
+    /// 生成条码图片
+    /// @param width 图片宽度
+    /// @param height 图片高度，二维码通常与宽度相同
+    pub fn to_image(&self, width: Length, height: Length) -> Result<DocxImage, DocxError> {
+        let (width_emu, height_emu) = (width.to_emu(), height.to_emu());
+        let image_data = self.to_png(width_emu, height_emu)?;
+        DocxImage::new_image_data_size(self.value(), image_data, "png", width_emu, height_emu)
+    }
+
+    /// 生成PNG图片，按目标DPI确定每个模块的像素数
+    /// @param width_emu 图片显示宽度（emu）
+    /// @param height_emu 图片显示高度（emu）
+    pub fn to_png(&self, width_emu: u64, height_emu: u64) -> Result<Vec<u8>, DocxError> {
+        // 显示宽度对应的像素数
+        let target_width = (width_emu as f64 * BARCODE_DPI / EMU).ceil().max(1.0) as usize;
+        let img = match self {
+            Barcode::QrCode(value) => {
+                let code = QrCode::new(value).map_err(|e| DocxError::Barcode(e.to_string()))?;
+                let modules = code.width() + QR_QUIET_ZONE * 2;
+                let scale = target_width.div_ceil(modules);
+                let colors = code.to_colors();
+                GrayImage::from_fn(
+                    (modules * scale) as u32,
+                    (modules * scale) as u32,
+                    |x, y| {
+                        let x = (x as usize / scale).checked_sub(QR_QUIET_ZONE);
+                        let y = (y as usize / scale).checked_sub(QR_QUIET_ZONE);
+                        let dark = match (x, y) {
+                            (Some(x), Some(y)) if x < code.width() && y < code.width() => {
+                                colors[y * code.width() + x] == Color::Dark
+                            }
+                            _ => false,
+                        };
+                        pixel(dark)
+                    },
+                )
+            }
+            Barcode::Code128(value) => bars_image(
+                &code128_modules(value)?,
+                target_width,
+                width_emu,
+                height_emu,
+            ),
+            Barcode::Ean13(value) => {
+                bars_image(&ean13_modules(value)?, target_width, width_emu, height_emu)
+            }
+        };
+        let mut image_data = Cursor::new(Vec::new());
+        img.write_to(&mut image_data, ImageFormat::Png)?;
+        Ok(image_data.into_inner())
+    }
+
+    /// 条码内容
+    pub fn value(&self) -> &str {
+        match self {
+            Barcode::QrCode(value) | Barcode::Code128(value) | Barcode::Ean13(value) => value,
+        }
+    }
+}
+
+/// 黑色或白色像素
+fn pixel(dark: bool) -> Luma<u8> {
+    match dark {
+        true => Luma([0]),
+        false => Luma([255]),
+    }
+}
+
+/// 生成一维条形码图片，左右留出空白
+/// @param modules 每个模块是否为条
+/// @param target_width 显示宽度对应的像素数
+/// @param width_emu 图片显示宽度（emu）
+/// @param height_emu 图片显示高度（emu）
+fn bars_image(modules: &[bool], target_width: usize, width_emu: u64, height_emu: u64) -> GrayImage {
+    let total = modules.len() + BAR_QUIET_ZONE * 2;
+    let scale = target_width.div_ceil(total);
+    let width = total * scale;
+    // 高度按显示宽高比计算
+    let height = (width as f64 * height_emu as f64 / width_emu.max(1) as f64).ceil() as usize;
+    GrayImage::from_fn(width as u32, height.max(1) as u32, |x, _| {
+        let dark = (x as usize / scale)
+            .checked_sub(BAR_QUIET_ZONE)
+            .and_then(|index| modules.get(index))
+            .copied()
+            .unwrap_or(false);
+        pixel(dark)
+    })
+}
+
+/// Code128编码，连续4位以上数字使用字符集C压缩，其他字符使用字符集B
+/// @param value 条码内容
+/// @return 每个模块是否为条
+fn code128_modules(value: &str) -> Result<Vec<bool>, DocxError> {
+    if value.is_empty() || !value.bytes().all(|b| (32..127).contains(&b)) {
+        return Err(DocxError::Barcode(format!(
+            "code128 only supports printable ascii: {}",
+            value
+        )));
+    }
+    let bytes = value.as_bytes();
+    let digits = |start: usize| {
+        bytes[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut codes = Vec::new();
+    let mut set_c = digits(0) >= 4 && digits(0) % 2 == 0;
+    codes.push(if set_c {
+        CODE128_START_C
+    } else {
+        CODE128_START_B
+    });
+    let mut index = 0;
+    while index < bytes.len() {
+        let run = digits(index);
+        if set_c {
+            if run >= 2 {
+                codes.push(((bytes[index] - b'0') * 10 + bytes[index + 1] - b'0') as usize);
+                index += 2;
+                continue;
+            }
+            codes.push(CODE128_TO_B);
+            set_c = false;
+        } else if run >= 4 && run % 2 == 0 {
+            codes.push(CODE128_TO_C);
+            set_c = true;
+            continue;
+        }
+        codes.push((bytes[index] - 32) as usize);
+        index += 1;
+    }
+    // 校验字符：起始字符加各字符值乘以位置，对103取余
+    let checksum = codes
+        .iter()
+        .enumerate()
+        .map(|(position, code)| position.max(1) * code)
+        .sum::<usize>()
+        % 103;
+    codes.push(checksum);
+    codes.push(CODE128_STOP);
+
+    let mut modules = Vec::new();
+    for code in codes {
+        for (index, width) in CODE128_PATTERNS[code].bytes().enumerate() {
+            // 条和空交替，从条开始
+            modules.extend(std::iter::repeat_n(index % 2 == 0, (width - b'0') as usize));
+        }
+    }
+    Ok(modules)
+}
+
+/// EAN-13编码
+/// @param value 12位或13位数字，13位时检查校验位
+/// @return 每个模块是否为条
+fn ean13_modules(value: &str) -> Result<Vec<bool>, DocxError> {
+    let digits: Vec<usize> = value
+        .chars()
+        .map(|c| c.to_digit(10).map(|d| d as usize))
+        .collect::<Option<_>>()
+        .filter(|digits: &Vec<usize>| matches!(digits.len(), 12 | 13))
+        .ok_or_else(|| DocxError::Barcode(format!("ean13 requires 12 or 13 digits: {}", value)))?;
+    // 奇数位乘1，偶数位乘3
+    let sum: usize = digits[..12]
+        .iter()
+        .enumerate()
+        .map(|(index, digit)| digit * if index % 2 == 0 { 1 } else { 3 })
+        .sum();
+    let check = (10 - sum % 10) % 10;
+    if digits.len() == 13 && digits[12] != check {
+        return Err(DocxError::Barcode(format!(
+            "ean13 check digit error: {}",
+            value
+        )));
+    }
+
+    let mut pattern = String::from("101");
+    for (index, digit) in digits[1..7].iter().enumerate() {
+        let code = EAN_L_CODES[*digit];
+        match EAN_PARITY[digits[0]].as_bytes()[index] {
+            b'L' => pattern.push_str(code),
+            _ => pattern.extend(code.chars().rev().map(invert)),
+        }
+    }
+    pattern.push_str("01010");
+    for digit in digits[7..12].iter().chain([&check]) {
+        pattern.extend(EAN_L_CODES[*digit].chars().map(invert));
+    }
+    pattern.push_str("101");
+    Ok(pattern.chars().map(|c| c == '1').collect())
+}
+
+/// 取反编码中的条和空
+fn invert(c: char) -> char {
+    match c {
+        '1' => '0',
+        _ => '1',
+    }
+}
@@ -0,0 +1,39 @@
+use crate::error::DocxError;
+
+/// 图片题注，写在图片下方的段落中，使用模板的题注样式和SEQ域，Word可以据此生成图表目录
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageCaption {
+    // 题注标签，同时作为SEQ域的序列名称，例如Figure、图
+    pub label: String,
+    // 题注文字，显示在编号之后
+    pub text: String,
+}
+
+impl ImageCaption {
+    /// 创建题注，标签默认为Figure
+    /// @param text 题注文字
+    pub fn new(text: &str) -> Self {
+        ImageCaption {
+            label: "Figure".to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    /// 设置题注标签
+    /// @param label 题注标签，例如图、表
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    /// 检查题注是否有效，SEQ域的序列名称不能为空或包含空白字符
+    pub fn validate(&self) -> Result<(), DocxError> {
+        if self.label.is_empty() || self.label.contains(char::is_whitespace) {
+            return Err(DocxError::Format(format!(
+                "invalid caption label {:?}",
+                self.label
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,57 @@
-use crate::docx::template::create_drawing_element;
+#[cfg(feature = "barcode")]
+use crate::barcode::Barcode;
+use crate::caption::ImageCaption;
+use crate::docx::caption::{CaptionCounter, write_caption};
+use crate::docx::compiled::CompiledDocument;
+use crate::docx::gallery::write_gallery;
+use crate::docx::layout::{DisplaySizes, image_size};
+use crate::docx::template::{create_drawing_element, svg_ext, svg_extension, write_xml};
 use crate::docx::word::*;
 use crate::error::DocxError;
-use crate::image::{DOCX_EMU, DocxImage};
-use crate::request::request_image_data;
+use crate::expr::evaluate;
+#[cfg(feature = "blocking")]
+use crate::fetcher::BlockingImageFetcher;
+#[cfg(feature = "http")]
+use crate::fetcher::{FetchResult, ImageFetcher};
+use crate::format::apply_filter;
+use crate::gallery::{GalleryImage, ImageGallery};
+#[cfg(feature = "http")]
+use crate::image::ImageFallback;
+use crate::image::{DOCX_EMU, DocxImage, decode_base64_image, guess_extension};
+use crate::size::ImageSize;
+#[cfg(feature = "barcode")]
+use crate::size::Length;
+use crate::style::ImageStyle;
+use crate::transform::ImageOptions;
+#[cfg(feature = "http")]
+use futures_util::{StreamExt, stream};
 use log::debug;
+#[cfg(feature = "http")]
+use log::warn;
 use quick_xml::Writer;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
-use reqwest::Client;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{Cursor, Read, Write};
-use std::time::Duration;
+use std::io::{Cursor, Read, Seek, Write};
+#[cfg(feature = "http")]
+use std::sync::{Arc, OnceLock};
 use zip::read::ZipFile;
 use zip::write::SimpleFileOptions;
-use zip::{ZipArchive, ZipWriter};
+use zip::{CompressionMethod, ZipWriter};
 
+mod batch;
+mod caption;
+mod compiled;
+mod gallery;
+mod layout;
+mod merge;
 mod template;
 mod word;
 
+pub use batch::{BatchError, BatchOptions, BatchOutput, BatchReport};
+pub use compiled::CompiledTemplate;
+pub use merge::MergeSeparator;
+
 static PREFIX_TAG: &str = "{{";
 static SUFFIX_TAG: &str = "}}";
 
@@ -26,10 +60,23 @@ pub struct DocxTemplate {
     text_replacements: HashMap<String, String>,
     // 待替换的图片
     image_replacements: HashMap<String, Option<DocxImage>>,
+    // 待替换的图片组，一个占位符替换为多张图片
+    galleries: HashMap<String, ImageGallery>,
     // 已经添加的图片路径
     images_map: HashMap<String, String>,
-    // 请求对象
-    client: Client,
+    // 图片内容哈希对应的关联编号，内容相同的图片共用一个关联编号
+    image_hashes: HashMap<[u8; 32], String>,
+    // 图片获取对象，首次使用时创建
+    #[cfg(feature = "http")]
+    fetcher: OnceLock<Arc<dyn ImageFetcher>>,
+    // 同步图片获取对象，首次使用时创建
+    #[cfg(feature = "blocking")]
+    blocking_fetcher: OnceLock<Arc<dyn BlockingImageFetcher>>,
+    // 在线图片获取失败时的处理方式
+    #[cfg(feature = "http")]
+    image_fallback: ImageFallback,
+    // 图片处理选项
+    image_options: ImageOptions,
 }
 
 impl DocxTemplate {
@@ -37,14 +84,39 @@ impl DocxTemplate {
         DocxTemplate {
             text_replacements: HashMap::new(),
             image_replacements: HashMap::new(),
+            galleries: HashMap::new(),
             images_map: HashMap::new(),
-            client: Client::builder()
-                .timeout(Duration::from_secs(100)) // 设置超时
-                .build()
-                .unwrap(),
+            image_hashes: HashMap::new(),
+            #[cfg(feature = "http")]
+            fetcher: OnceLock::new(),
+            #[cfg(feature = "blocking")]
+            blocking_fetcher: OnceLock::new(),
+            #[cfg(feature = "http")]
+            image_fallback: ImageFallback::default(),
+            image_options: ImageOptions::default(),
         }
     }
 
+    /// 设置图片获取对象，用于对象存储、内部文件服务、缓存或测试
+    /// @param fetcher 图片获取对象
+    #[cfg(feature = "http")]
+    pub fn set_image_fetcher(&mut self, fetcher: impl ImageFetcher + 'static) {
+        self.fetcher = OnceLock::from(Arc::new(fetcher) as Arc<dyn ImageFetcher>);
+    }
+
+    /// 设置同步图片获取对象
+    /// @param fetcher 同步图片获取对象
+    #[cfg(feature = "blocking")]
+    pub fn set_blocking_image_fetcher(&mut self, fetcher: impl BlockingImageFetcher + 'static) {
+        self.blocking_fetcher = OnceLock::from(Arc::new(fetcher) as Arc<dyn BlockingImageFetcher>);
+    }
+
+    /// 设置图片处理选项，对之后添加的图片生效
+    /// @param options 图片处理选项
+    pub fn set_image_options(&mut self, options: ImageOptions) {
+        self.image_options = options;
+    }
+
     /// 添加待替换的字符以及对应的值
     /// @param placeholder 待替换的字符串
     /// @param value 替换的值
@@ -69,19 +141,9 @@ impl DocxTemplate {
             }
             Some(file_path) => {
                 // 判断是否添加过该图片
-                if self.images_map.contains_key(file_path) {
-                    let old_placeholder = &self.images_map[file_path];
-                    let image_option = &self.image_replacements[old_placeholder];
-                    // 插入图片到属性中
-                    self.image_replacements
-                        .insert(placeholder.to_string(), image_option.clone());
-                } else {
-                    // 收集添加的图片路径
-                    self.images_map
-                        .insert(file_path.to_string(), placeholder.to_string());
-                    // 插入图片到属性中
-                    self.image_replacements
-                        .insert(placeholder.to_string(), Some(DocxImage::new(file_path)?));
+                if !self.reuse_image(placeholder, file_path, None) {
+                    let docx_image = DocxImage::new(file_path)?;
+                    self.insert_image(placeholder, file_path, docx_image);
                 }
             }
         }
@@ -112,26 +174,9 @@ impl DocxTemplate {
                 let width_emu = (width * DOCX_EMU) as u64;
                 let height_emu = (height * DOCX_EMU) as u64;
                 // 判断是否添加过该图片
-                if self.images_map.contains_key(file_path) {
-                    let old_placeholder = &self.images_map[file_path];
-                    let image_option = &self.image_replacements[old_placeholder];
-
-                    if let Some(image) = image_option {
-                        let docx_image =
-                            DocxImage::clone_image_reset_size(image, width_emu, height_emu);
-                        // 插入图片到属性中
-                        self.image_replacements
-                            .insert(placeholder.to_string(), Some(docx_image));
-                    }
-                } else {
-                    // 收集添加的图片路径
-                    self.images_map
-                        .insert(file_path.to_string(), placeholder.to_string());
-                    // 插入图片到属性中
-                    self.image_replacements.insert(
-                        placeholder.to_string(),
-                        Some(DocxImage::new_size(file_path, width_emu, height_emu)?),
-                    );
+                if !self.reuse_image(placeholder, file_path, Some((width_emu, height_emu))) {
+                    let docx_image = DocxImage::new_size(file_path, width_emu, height_emu)?;
+                    self.insert_image(placeholder, file_path, docx_image);
                 }
             }
         }
@@ -139,9 +184,188 @@ impl DocxTemplate {
         Ok(())
     }
 
+    /// 添加内存中的图片数据，例如生成的图表、二维码，根据文件头判断图片格式
+    /// @param placeholder 替换的字符串
+    /// @param image_data 图片数据
+    /// @param size 图片的宽度和高度(厘米)，None时使用图片原始大小
+    pub fn add_image_bytes_replacement(
+        &mut self,
+        placeholder: &str,
+        image_data: impl Into<Vec<u8>>,
+        size: Option<(f32, f32)>,
+    ) -> Result<(), DocxError> {
+        let image_data = image_data.into();
+        let image_ext = guess_extension(&image_data)?;
+        let docx_image = match size {
+            Some((width, height)) => {
+                // 将厘米单位换算成emu
+                let width_emu = (width * DOCX_EMU) as u64;
+                let height_emu = (height * DOCX_EMU) as u64;
+                DocxImage::new_image_data_size(
+                    placeholder,
+                    image_data,
+                    image_ext,
+                    width_emu,
+                    height_emu,
+                )?
+            }
+            None => DocxImage::new_image_data(placeholder, image_data, image_ext)?,
+        };
+        self.insert_image(placeholder, &memory_image_key(placeholder), docx_image);
+        Ok(())
+    }
+
+    /// 添加本地生成的二维码或条形码，条码图片不按处理选项缩小，避免条变模糊
+    /// @param placeholder 替换的字符串
+    /// @param barcode 二维码或条形码
+    /// @param width 图片宽度
+    /// @param height 图片高度
+    #[cfg(feature = "barcode")]
+    pub fn add_barcode_replacement(
+        &mut self,
+        placeholder: &str,
+        barcode: &Barcode,
+        width: Length,
+        height: Length,
+    ) -> Result<(), DocxError> {
+        let docx_image = self.share_media(barcode.to_image(width, height)?);
+        self.images_map
+            .insert(memory_image_key(placeholder), placeholder.to_string());
+        self.image_replacements
+            .insert(placeholder.to_string(), Some(docx_image));
+        Ok(())
+    }
+
+    /// 按适应方式设置已添加图片的大小，例如等比例缩放、填满裁剪、固定宽度或高度
+    /// @param placeholder 替换的字符串
+    /// @param size 图片大小的适应方式
+    pub fn set_image_size(&mut self, placeholder: &str, size: ImageSize) -> Result<(), DocxError> {
+        match self.image_replacements.get_mut(placeholder) {
+            Some(Some(docx_image)) => docx_image.fit(size),
+            Some(None) => Ok(()),
+            None => Err(DocxError::ImageNotFound(placeholder.to_string())),
+        }
+    }
+
+    /// 设置已添加图片的样式：旋转、翻转、裁剪、边框和阴影
+    /// @param placeholder 替换的字符串
+    /// @param style 图片样式
+    pub fn set_image_style(
+        &mut self,
+        placeholder: &str,
+        style: ImageStyle,
+    ) -> Result<(), DocxError> {
+        style.validate()?;
+        match self.image_replacements.get_mut(placeholder) {
+            Some(Some(docx_image)) => {
+                docx_image.style = style;
+                Ok(())
+            }
+            Some(None) => Ok(()),
+            None => Err(DocxError::ImageNotFound(placeholder.to_string())),
+        }
+    }
+
+    /// 设置已添加图片的题注，题注写在图片下方，使用模板的题注样式和SEQ域编号
+    /// @param placeholder 替换的字符串
+    /// @param caption 图片题注
+    pub fn set_image_caption(
+        &mut self,
+        placeholder: &str,
+        caption: ImageCaption,
+    ) -> Result<(), DocxError> {
+        caption.validate()?;
+        match self.image_replacements.get_mut(placeholder) {
+            Some(Some(docx_image)) => {
+                docx_image.caption = Some(caption);
+                Ok(())
+            }
+            Some(None) => Ok(()),
+            None => Err(DocxError::ImageNotFound(placeholder.to_string())),
+        }
+    }
+
+    /// 添加多张图片替换同一占位符，图片组为空时清除占位符
+    /// @param placeholder 替换的字符串
+    /// @param gallery 图片组
+    pub fn add_image_gallery_replacement(&mut self, placeholder: &str, gallery: ImageGallery) {
+        let images = gallery
+            .images
+            .into_iter()
+            .map(|item| GalleryImage {
+                image: self.share_media(item.image.apply_options(&self.image_options)),
+                caption: item.caption,
+            })
+            .collect();
+        self.galleries
+            .insert(placeholder.to_string(), ImageGallery { images, ..gallery });
+    }
+
+    /// 添加data URI或base64字符串格式的图片，例如前端提交的签名
+    /// @param placeholder 替换的字符串
+    /// @param value data:image/png;base64,... 或base64字符串
+    pub fn add_image_base64_replacement(
+        &mut self,
+        placeholder: &str,
+        value: Option<&str>,
+    ) -> Result<(), DocxError> {
+        match value {
+            None => {
+                // 插入图片到属性中
+                self.image_replacements
+                    .insert(placeholder.to_string(), None);
+            }
+            Some(value) => {
+                let (image_data, image_ext) = decode_base64_image(value)?;
+                let docx_image = DocxImage::new_image_data(placeholder, image_data, &image_ext)?;
+                self.insert_image(placeholder, &memory_image_key(placeholder), docx_image);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 添加data URI或base64字符串格式的图片
+    /// @param placeholder 替换的字符串
+    /// @param value data:image/png;base64,... 或base64字符串
+    /// @param width 图片的宽度(厘米)
+    /// @param height 图片的高度(厘米)
+    pub fn add_image_base64_size_replacement(
+        &mut self,
+        placeholder: &str,
+        value: Option<&str>,
+        width: f32,
+        height: f32,
+    ) -> Result<(), DocxError> {
+        match value {
+            None => {
+                // 插入图片到属性中
+                self.image_replacements
+                    .insert(placeholder.to_string(), None);
+            }
+            Some(value) => {
+                // 将厘米单位换算成emu
+                let width_emu = (width * DOCX_EMU) as u64;
+                let height_emu = (height * DOCX_EMU) as u64;
+                let (image_data, image_ext) = decode_base64_image(value)?;
+                let docx_image = DocxImage::new_image_data_size(
+                    placeholder,
+                    image_data,
+                    &image_ext,
+                    width_emu,
+                    height_emu,
+                )?;
+                self.insert_image(placeholder, &memory_image_key(placeholder), docx_image);
+            }
+        }
+
+        Ok(())
+    }
+
     /// 添加待替换的图片，替换的图片大小默认6.09*5.9厘米
     /// @param placeholder 替换的字符串
     /// @param image_url 图片路径
+    #[cfg(feature = "http")]
     pub async fn add_image_url_replacement(
         &mut self,
         placeholder: &str,
@@ -155,23 +379,13 @@ impl DocxTemplate {
             }
             Some(url) => {
                 // 判断是否添加过该图片
-                if self.images_map.contains_key(url) {
-                    let old_placeholder = &self.images_map[url];
-                    let image_option = &self.image_replacements[old_placeholder];
-                    // 插入图片到属性中
-                    self.image_replacements
-                        .insert(placeholder.to_string(), image_option.clone());
-                } else {
-                    // 收集添加的图片路径
-                    self.images_map
-                        .insert(url.to_string(), placeholder.to_string());
+                if !self.reuse_image(placeholder, url, None) {
                     // 发送请求
-                    let (image_data, image_ext) = request_image_data(&self.client, url).await?;
-                    // 插入图片到属性中
-                    self.image_replacements.insert(
-                        placeholder.to_string(),
-                        Some(DocxImage::new_image_data(url, image_data, &image_ext)?),
-                    );
+                    let result = self.image_fetcher()?.fetch(url).await;
+                    let result = result.and_then(|(image_data, image_ext)| {
+                        DocxImage::new_image_data(url, image_data, &image_ext)
+                    });
+                    self.insert_fetched_image(placeholder, url, None, result)?;
                 }
             }
         }
@@ -184,6 +398,7 @@ impl DocxTemplate {
     /// @param image_url 图片路径
     /// @param width 图片的宽度(厘米)
     /// @param height 图片的高度(厘米)
+    #[cfg(feature = "http")]
     pub async fn add_image_url_size_replacement(
         &mut self,
         placeholder: &str,
@@ -202,28 +417,160 @@ impl DocxTemplate {
                 let width_emu = (width * DOCX_EMU) as u64;
                 let height_emu = (height * DOCX_EMU) as u64;
                 // 判断是否添加过该图片
-                if self.images_map.contains_key(url) {
-                    let old_placeholder = &self.images_map[url];
-                    let image_option = &self.image_replacements[old_placeholder];
-                    if let Some(image) = image_option {
-                        let docx_image =
-                            DocxImage::clone_image_reset_size(image, width_emu, height_emu);
-                        // 插入图片到属性中
-                        self.image_replacements
-                            .insert(placeholder.to_string(), Some(docx_image));
-                    }
-                } else {
-                    self.images_map
-                        .insert(url.to_string(), placeholder.to_string());
+                if !self.reuse_image(placeholder, url, Some((width_emu, height_emu))) {
+                    // 发送请求
+                    let result = self.image_fetcher()?.fetch(url).await;
+                    let result = result.and_then(|(image_data, image_ext)| {
+                        DocxImage::new_image_data_size(
+                            url, image_data, &image_ext, width_emu, height_emu,
+                        )
+                    });
+                    let size = Some((width_emu, height_emu));
+                    self.insert_fetched_image(placeholder, url, size, result)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 批量添加待替换的在线图片，相同的图片路径只获取一次，并发获取图片
+    /// 获取失败的图片不会影响其他图片，全部处理后返回第一个错误
+    /// @param images 替换的字符串和图片路径
+    /// @param concurrency 最大并发数量
+    #[cfg(feature = "http")]
+    pub async fn add_image_url_replacements<'a, I>(
+        &mut self,
+        images: I,
+        concurrency: usize,
+    ) -> Result<(), DocxError>
+    where
+        I: IntoIterator<Item = (&'a str, Option<&'a str>)>,
+    {
+        let images: Vec<(&str, Option<&str>)> = images.into_iter().collect();
+        // 收集需要获取的图片路径，去掉重复和已经添加过的图片
+        let mut urls: Vec<&str> = Vec::new();
+        for url in images.iter().filter_map(|(_, url)| *url) {
+            if !self.images_map.contains_key(url) && !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+        // 并发获取图片
+        let mut fetched: HashMap<&str, FetchResult> = HashMap::new();
+        if !urls.is_empty() {
+            let fetcher = self.image_fetcher()?;
+            fetched = stream::iter(urls)
+                .map(|url| {
+                    let fetcher = &fetcher;
+                    async move { (url, fetcher.fetch(url).await) }
+                })
+                .buffer_unordered(concurrency.max(1))
+                .collect()
+                .await;
+        }
+
+        // 获取失败的图片路径和错误信息
+        let mut failed: HashMap<&str, String> = HashMap::new();
+        let mut first_error = None;
+        for (placeholder, image_url) in images {
+            let Some(url) = image_url else {
+                // 插入图片到属性中
+                self.image_replacements
+                    .insert(placeholder.to_string(), None);
+                continue;
+            };
+            // 判断是否添加过该图片
+            if self.reuse_image(placeholder, url, None) {
+                continue;
+            }
+            let result = match fetched.remove(url) {
+                Some(result) => result.and_then(|(image_data, image_ext)| {
+                    DocxImage::new_image_data(url, image_data, &image_ext)
+                }),
+                // 同一图片路径已经获取失败，返回相同的错误信息
+                None => Err(DocxError::FetchFailed(
+                    failed.get(url).cloned().unwrap_or_else(|| url.to_string()),
+                )),
+            };
+            if let Err(e) = &result {
+                failed.entry(url).or_insert_with(|| e.to_string());
+            }
+            if let Err(e) = self.insert_fetched_image(placeholder, url, None, result) {
+                first_error.get_or_insert(e);
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// 同步添加待替换的在线图片，用于非异步程序
+    /// @param placeholder 替换的字符串
+    /// @param image_url 图片路径
+    #[cfg(feature = "blocking")]
+    pub fn add_image_url_replacement_blocking(
+        &mut self,
+        placeholder: &str,
+        image_url: Option<&str>,
+    ) -> Result<(), DocxError> {
+        match image_url {
+            None => {
+                // 插入图片到属性中
+                self.image_replacements
+                    .insert(placeholder.to_string(), None);
+            }
+            Some(url) => {
+                // 判断是否添加过该图片
+                if !self.reuse_image(placeholder, url, None) {
+                    // 发送请求
+                    let result = self.blocking_image_fetcher()?.fetch(url);
+                    let result = result.and_then(|(image_data, image_ext)| {
+                        DocxImage::new_image_data(url, image_data, &image_ext)
+                    });
+                    self.insert_fetched_image(placeholder, url, None, result)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 同步添加待替换的在线图片，用于非异步程序
+    /// @param placeholder 替换的字符串
+    /// @param image_url 图片路径
+    /// @param width 图片的宽度(厘米)
+    /// @param height 图片的高度(厘米)
+    #[cfg(feature = "blocking")]
+    pub fn add_image_url_size_replacement_blocking(
+        &mut self,
+        placeholder: &str,
+        image_url: Option<&str>,
+        width: f32,
+        height: f32,
+    ) -> Result<(), DocxError> {
+        match image_url {
+            None => {
+                // 插入图片到属性中
+                self.image_replacements
+                    .insert(placeholder.to_string(), None);
+            }
+            Some(url) => {
+                // 将厘米单位换算成emu
+                let width_emu = (width * DOCX_EMU) as u64;
+                let height_emu = (height * DOCX_EMU) as u64;
+                // 判断是否添加过该图片
+                if !self.reuse_image(placeholder, url, Some((width_emu, height_emu))) {
                     // 发送请求
-                    let (image_data, image_ext) = request_image_data(&self.client, url).await?;
-                    // 插入图片到属性中
-                    self.image_replacements.insert(
-                        placeholder.to_string(),
-                        Some(DocxImage::new_image_data_size(
+                    let result = self.blocking_image_fetcher()?.fetch(url);
+                    let result = result.and_then(|(image_data, image_ext)| {
+                        DocxImage::new_image_data_size(
                             url, image_data, &image_ext, width_emu, height_emu,
-                        )?),
-                    );
+                        )
+                    });
+                    let size = Some((width_emu, height_emu));
+                    self.insert_fetched_image(placeholder, url, size, result)?;
                 }
             }
         }
@@ -231,6 +578,131 @@ impl DocxTemplate {
         Ok(())
     }
 
+    /// 获取图片获取对象，未设置时默认使用HttpImageFetcher
+    #[cfg(feature = "http")]
+    fn image_fetcher(&self) -> Result<Arc<dyn ImageFetcher>, DocxError> {
+        if let Some(fetcher) = self.fetcher.get() {
+            return Ok(fetcher.clone());
+        }
+        let fetcher: Arc<dyn ImageFetcher> = Arc::new(crate::request::HttpImageFetcher::new()?);
+        Ok(self.fetcher.get_or_init(|| fetcher).clone())
+    }
+
+    /// 获取同步图片获取对象，未设置时默认使用BlockingHttpImageFetcher
+    #[cfg(feature = "blocking")]
+    fn blocking_image_fetcher(&self) -> Result<&dyn BlockingImageFetcher, DocxError> {
+        if let Some(fetcher) = self.blocking_fetcher.get() {
+            return Ok(fetcher.as_ref());
+        }
+        let fetcher: Arc<dyn BlockingImageFetcher> =
+            Arc::new(crate::request::BlockingHttpImageFetcher::new()?);
+        Ok(self.blocking_fetcher.get_or_init(|| fetcher).as_ref())
+    }
+
+    /// 设置在线图片获取失败时的处理方式，默认返回错误
+    /// @param fallback 处理方式
+    #[cfg(feature = "http")]
+    pub fn set_image_fallback(&mut self, fallback: ImageFallback) {
+        self.image_fallback = fallback;
+    }
+
+    /// 添加获取到的在线图片，获取失败时按设置的处理方式处理
+    /// @param placeholder 替换的字符串
+    /// @param url 图片路径
+    /// @param size 图片大小（emu）
+    /// @param result 图片获取结果
+    #[cfg(feature = "http")]
+    fn insert_fetched_image(
+        &mut self,
+        placeholder: &str,
+        url: &str,
+        size: Option<(u64, u64)>,
+        result: Result<DocxImage, DocxError>,
+    ) -> Result<(), DocxError> {
+        let error = match result {
+            Ok(docx_image) => {
+                self.insert_image(placeholder, url, docx_image);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        match self.image_fallback.clone() {
+            ImageFallback::Fail => return Err(error),
+            ImageFallback::Blank => {
+                warn!("{} 图片获取失败，不显示图片: {}", url, error);
+                self.image_replacements
+                    .insert(placeholder.to_string(), None);
+            }
+            ImageFallback::Placeholder(image) => {
+                warn!("{} 图片获取失败，使用占位图片: {}", url, error);
+                // 占位图片只写入一次
+                let image_key = format!("fallback:{}", image.relation_id);
+                if !self.reuse_image(placeholder, &image_key, size) {
+                    let image = match size {
+                        Some((width, height)) => {
+                            DocxImage::clone_image_reset_size(&image, width, height)
+                        }
+                        None => *image,
+                    };
+                    self.insert_image(placeholder, &image_key, image);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 判断是否添加过该图片，添加过则直接复用
+    /// @param placeholder 替换的字符串
+    /// @param image_key 图片路径
+    /// @param size 重新设置的图片大小（emu）
+    /// @return 是否已复用
+    fn reuse_image(
+        &mut self,
+        placeholder: &str,
+        image_key: &str,
+        size: Option<(u64, u64)>,
+    ) -> bool {
+        let Some(old_placeholder) = self.images_map.get(image_key) else {
+            return false;
+        };
+        let image_option = match (&self.image_replacements[old_placeholder], size) {
+            (Some(image), Some((width, height))) => {
+                Some(DocxImage::clone_image_reset_size(image, width, height))
+            }
+            (image_option, _) => image_option.clone(),
+        };
+        // 插入图片到属性中
+        self.image_replacements
+            .insert(placeholder.to_string(), image_option);
+        true
+    }
+
+    /// 添加新的图片
+    /// @param placeholder 替换的字符串
+    /// @param image_key 图片路径
+    /// @param docx_image 图片对象
+    fn insert_image(&mut self, placeholder: &str, image_key: &str, docx_image: DocxImage) {
+        // 按处理选项处理图片
+        let docx_image = self.share_media(docx_image.apply_options(&self.image_options));
+        // 收集添加的图片路径
+        self.images_map
+            .insert(image_key.to_string(), placeholder.to_string());
+        // 插入图片到属性中
+        self.image_replacements
+            .insert(placeholder.to_string(), Some(docx_image));
+    }
+
+    /// 内容相同的图片共用同一个关联编号，文档中只保存一份
+    /// @param docx_image 图片对象
+    fn share_media(&mut self, mut docx_image: DocxImage) -> DocxImage {
+        let relation_id = self
+            .image_hashes
+            .entry(docx_image.content_hash())
+            .or_insert_with(|| docx_image.relation_id.clone());
+        docx_image.relation_id = relation_id.clone();
+        docx_image
+    }
+
     /// 处理模板
     /// @param template_path 模板路径
     /// @param output_path 输出路径
@@ -239,241 +711,376 @@ impl DocxTemplate {
         template_path: &str,
         output_path: &str,
     ) -> Result<(), DocxError> {
-        // 1. 打开模板文件
-        let template_file = File::open(template_path)?;
-        let mut archive = ZipArchive::new(template_file)?;
-
-        // 2. 创建输出文件
+        // 1. 读取并解析模板文件
+        let template = CompiledTemplate::load(template_path)?;
+        // 2. 创建输出文件，写入替换后的内容
         let output_file = File::create(output_path)?;
-        let mut zip_writer = ZipWriter::new(output_file);
-
-        // 3. 遍历ZIP中的文件
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            // 文件内容
-            let mut contents = Vec::new();
-            // 读取文件内容到数组中
-            file.read_to_end(&mut contents)?;
-            // 匹配文件类型
-            match file.name() {
-                x if x == WORD_DOCUMENT => {
-                    // 处理文档主内容,替换模板内容
-                    contents = self.process_document_xml(&contents)?;
+        template.render_to(self, output_file)
+    }
+
+    /// 处理标签，模板中已有图片的可选文字（descr）或标题（title）为占位符时替换图片数据，
+    /// 保留图片框的大小、位置、裁剪和效果；占位符对应的图片为空时保留原图片并去掉占位符，
+    /// 不支持替换为图片组
+    /// @param element 标签
+    /// @param swap 待替换的图片
+    fn process_element<'a>(
+        &'a self,
+        element: &mut BytesStart,
+        swap: &mut Option<&'a DocxImage>,
+    ) -> Result<(), DocxError> {
+        match element.name().as_ref() {
+            // 图片属性，判断可选文字或标题是否为图片占位符
+            b"wp:docPr" => {
+                *swap = None;
+                let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+                let mut cleared = BytesStart::new(name);
+                let mut matched = false;
+                for attribute in element.attributes().flatten() {
+                    if matches!(attribute.key.as_ref(), b"descr" | b"title") {
+                        let value = attribute.unescape_value()?;
+                        let placeholder = value.trim();
+                        if self.galleries.contains_key(placeholder) {
+                            return Err(DocxError::InvalidTemplate(format!(
+                                "image gallery {} is not supported in image alt text",
+                                placeholder
+                            )));
+                        }
+                        if let Some(image_option) = self.image_replacements.get(placeholder) {
+                            *swap = image_option.as_ref();
+                            matched = true;
+                            // 去掉生成文档中的占位符
+                            continue;
+                        }
+                    }
+                    cleared.push_attribute(attribute);
                 }
-                x if x == WORD_RELS_DOCUMENT => {
-                    // 处理关系文件
-                    contents = self.process_rels_xml(&contents)?;
+                if matched {
+                    *element = cleared;
                 }
-                &_ => {}
             }
-            // 写入新文件
-            writer_file(&mut zip_writer, &file, &contents)?
-        }
-
-        // 4. 添加新的图片文件
-        for replacement in self.images_map.values() {
-            if let Some(Some(replacement)) = self.image_replacements.get(replacement) {
-                writer_image(&mut zip_writer, replacement)?;
+            // 图片数据引用，替换为新图片的关联编号，SVG引用替换为新图片的SVG关联编号
+            b"a:blip" | b"v:imagedata" | b"asvg:svgBlip" => {
+                if let Some(docx_image) = *swap {
+                    let relation_id = match element.name().as_ref() {
+                        b"asvg:svgBlip" => docx_image.svg_relation_id(),
+                        _ => docx_image.relation_id.clone(),
+                    };
+                    let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+                    let mut swapped = BytesStart::new(name);
+                    for attribute in element.attributes().flatten() {
+                        match attribute.key.as_ref() {
+                            b"r:embed" | b"r:id" => swapped
+                                .push_attribute((attribute.key.as_ref(), relation_id.as_bytes())),
+                            _ => swapped.push_attribute(attribute),
+                        }
+                    }
+                    *element = swapped;
+                }
             }
+            _ => {}
         }
-        // 将内容写入压缩文件（docx）
-        zip_writer.finish()?;
-        Ok(())
-    }
-
-    fn process_element(&self, _element: &mut BytesStart) -> Result<(), DocxError> {
-        let tag = String::from_utf8_lossy(_element.name().as_ref()).to_string();
-        debug!("{:?}", tag);
         Ok(())
     }
 
     /// 处理文件内容
-    /// @param contents 文件内容数组
-    fn process_document_xml(&self, contents: &[u8]) -> Result<Vec<u8>, DocxError> {
+    /// @param document 预解析的文件内容
+    /// @param caption_style 题注样式编号
+    /// @param sizes 记录图片的显示大小
+    /// @param captions 题注编号，合并文档时在多份内容之间连续编号
+    fn process_document_xml(
+        &self,
+        document: &CompiledDocument,
+        caption_style: &str,
+        sizes: &mut DisplaySizes,
+        captions: &mut CaptionCounter,
+    ) -> Result<Vec<u8>, DocxError> {
         // 创建xml写对象
         let mut xml_writer = Writer::new(Cursor::new(Vec::new()));
-        // 读取xml文件的内容
-        let mut reader = quick_xml::Reader::from_reader(contents);
-        // reader.config_mut().trim_text(true);
-        // 缓存数组
-        let mut buf = Vec::new();
-        // 图片对应的字符串占位符
-        let mut current_placeholder = String::new();
-        // 循环读取xml数据
-        loop {
-            // 读取数据
-            match reader.read_event_into(&mut buf)? {
+        // 图片对应的字符串占位符，在段落结束时写入图片
+        let mut image_placeholder: Option<String> = None;
+        // 图片占位符之后被跳过的标签层数
+        let mut skipped_depth = 0;
+        // 包含占位符的文本
+        let mut spans = document.spans.iter().peekable();
+        // 页面和单元格宽度，用于限制默认大小图片的宽度
+        let mut layout = document.layout.clone();
+        // 模板中已有图片需要替换的图片
+        let mut swap = None;
+        // 模板中已有图片的显示大小
+        let mut extent = None;
+        // 替换的图片为SVG时，a:blip中是否还需要添加SVG引用
+        let mut svg_pending = false;
+        // 循环处理xml数据
+        for (index, event) in document.events.iter().enumerate() {
+            layout.update(event);
+            // 模板中已有的题注编号
+            captions.update(event);
+            // 占位符被拆分到多个文本块时，中间的标签和文本不写入，合并后的文本在最后一个文本的位置写入
+            let span = spans.peek().filter(|span| span.start <= index);
+            let in_span = span.is_some_and(|span| index < span.end);
+            // 图片占位符所在段落的其余内容不写入
+            let skip = in_span || image_placeholder.is_some();
+            match event.borrow() {
                 Event::Start(e) => {
                     let mut element = e.to_owned();
-                    self.process_element(&mut element)?;
-                    // 如果为空，写入标签头
-                    if current_placeholder.is_empty() {
+                    self.process_element(&mut element, &mut swap)?;
+                    record_extent(&element, swap, &mut extent, sizes);
+                    match e.name().as_ref() {
+                        b"a:blip" => {
+                            svg_pending = swap.is_some_and(|image| image.svg_data.is_some());
+                        }
+                        // 模板中已有SVG引用，已替换关联编号
+                        b"asvg:svgBlip" => svg_pending = false,
+                        _ => {}
+                    }
+                    if image_placeholder.is_some() && !in_span {
+                        skipped_depth += 1;
+                    }
+                    if !skip {
                         xml_writer.write_event(Event::Start(element))?;
                     }
                 }
                 Event::Text(e) => {
-                    // 读取标签的内容
-                    let mut text = e.unescape()?.into_owned();
-                    // 判断是否有替换字符串开头内容"{{"
-                    if text.contains(PREFIX_TAG) {
-                        // 判断是否包含结束字符串}}
-                        if text.contains(SUFFIX_TAG) {
-                            // 1、替换文本占位符操作
-                            self.process_text(&mut text);
-                            // 2、替换图片占位符操作
-                            if self.image_replacements.contains_key(&text) {
-                                current_placeholder.push_str(&text);
-                            } else {
-                                xml_writer
-                                    .write_event(Event::Text(BytesText::new(text.as_str())))?;
-                            }
-                        } else {
-                            // 将字符串保存
-                            current_placeholder.push_str(&text);
+                    let Some(span) = span.filter(|span| span.end == index) else {
+                        // 没有占位符的文本原样写入
+                        if !skip {
+                            xml_writer.write_event(Event::Text(e))?;
                         }
+                        continue;
+                    };
+                    let mut text = span.text.clone();
+                    spans.next();
+                    if image_placeholder.is_some() {
+                        continue;
+                    }
+                    // 1、替换文本占位符操作
+                    self.process_text(&mut text);
+                    // 2、替换图片占位符操作，段落结束时写入图片
+                    if self.is_image_placeholder(&text) {
+                        image_placeholder = Some(text);
                     } else {
-                        // 判断current_placeholder字符串是否有内容
-                        if current_placeholder.is_empty() {
-                            // 将原有字符串写入文档
-                            xml_writer.write_event(Event::Text(BytesText::new(text.as_str())))?;
-                        } else {
-                            // 将字符串写入
-                            current_placeholder.push_str(text.as_str());
-                            // 判断是否有结束字符串}}
-                            if current_placeholder.contains(PREFIX_TAG)
-                                && current_placeholder.contains(SUFFIX_TAG)
-                            {
-                                // 1、替换文本占位符操作
-                                self.process_text(&mut current_placeholder);
-                                // 2、如果不包含写入数据
-                                if !self.image_replacements.contains_key(&current_placeholder) {
-                                    xml_writer.write_event(Event::Text(BytesText::new(
-                                        current_placeholder.as_str(),
-                                    )))?;
-                                    // 清理数据
-                                    current_placeholder.clear();
-                                }
-                            }
-                        }
+                        xml_writer.write_event(Event::Text(BytesText::new(text.as_str())))?;
                     }
                 }
                 Event::End(e) => {
-                    // 判断是否为空，为空，直接添加结尾标签
-                    if current_placeholder.is_empty() {
-                        xml_writer.write_event(Event::End(e))?;
-                    } else if current_placeholder.contains(PREFIX_TAG)
-                        && current_placeholder.contains(SUFFIX_TAG)
-                    {
-                        // 判断是否为段落
-                        if e.name().as_ref() == WORD_PARAGRAPH_TAG {
-                            // 判断是否为完整替换字符串
-                            if let Some(Some(docx_image)) =
-                                self.image_replacements.get(&current_placeholder)
-                            {
-                                // 替换占位符为图片
-                                create_drawing_element(
-                                    &mut xml_writer,
-                                    &docx_image.relation_id,
-                                    docx_image.width,
-                                    docx_image.height,
-                                )?;
+                    // 图片所在的文本块结束
+                    if e.name().as_ref() == b"w:r" {
+                        swap = None;
+                    }
+                    if in_span {
+                        continue;
+                    }
+                    if skipped_depth > 0 {
+                        skipped_depth -= 1;
+                        continue;
+                    }
+                    // a:blip中有其他扩展项时，将SVG引用加入已有的扩展列表，没有扩展列表时添加
+                    if let (true, Some(docx_image)) = (svg_pending, swap) {
+                        let svg_relation_id = docx_image.svg_relation_id();
+                        match e.name().as_ref() {
+                            b"a:extLst" => {
+                                write_xml(&mut xml_writer, &svg_ext(&svg_relation_id))?;
+                                svg_pending = false;
                             }
-                            // 清除字符串
-                            current_placeholder.clear();
+                            b"a:blip" => {
+                                write_xml(&mut xml_writer, &svg_extension(&svg_relation_id))?;
+                                svg_pending = false;
+                            }
+                            _ => {}
                         }
-                        // 写入结尾标签
-                        xml_writer.write_event(Event::End(e))?;
                     }
+                    // 图片占位符所在段落结束，写入图片
+                    if e.name().as_ref() == WORD_PARAGRAPH_TAG {
+                        if let Some(placeholder) = image_placeholder.take() {
+                            self.write_placeholder_image(
+                                &mut xml_writer,
+                                &placeholder,
+                                layout.max_width(index),
+                                caption_style,
+                                sizes,
+                                captions,
+                            )?;
+                        }
+                    }
+                    xml_writer.write_event(Event::End(e))?;
                 }
                 Event::Eof => break,
                 Event::Empty(e) => {
-                    // 如果为空写入文档
-                    if current_placeholder.is_empty() {
-                        xml_writer.write_event(Event::Empty(e))?;
+                    let mut element = e.to_owned();
+                    let svg_data = swap.map(|docx_image| &docx_image.svg_data);
+                    // 替换的图片没有SVG时，去掉模板中原有图片的SVG引用
+                    if e.name().as_ref() == b"asvg:svgBlip" && svg_data.is_some_and(Option::is_none)
+                    {
+                        continue;
+                    }
+                    self.process_element(&mut element, &mut swap)?;
+                    record_extent(&element, swap, &mut extent, sizes);
+                    if e.name().as_ref() == b"asvg:svgBlip" {
+                        svg_pending = false;
+                    }
+                    if skip {
+                        continue;
+                    }
+                    // 替换的图片为SVG时，为没有扩展标签的a:blip添加SVG引用
+                    match swap {
+                        Some(docx_image)
+                            if e.name().as_ref() == b"a:blip" && docx_image.svg_data.is_some() =>
+                        {
+                            xml_writer.write_event(Event::Start(element))?;
+                            write_xml(
+                                &mut xml_writer,
+                                &svg_extension(&docx_image.svg_relation_id()),
+                            )?;
+                            xml_writer.write_event(Event::End(BytesEnd::new("a:blip")))?;
+                        }
+                        // 如果为空写入文档
+                        _ => xml_writer.write_event(Event::Empty(element))?,
                     }
                 }
                 e => {
-                    xml_writer.write_event(e)?;
+                    if !skip {
+                        xml_writer.write_event(e)?;
+                    }
                 }
             }
-            buf.clear();
         }
         // 返回文件数组
         Ok(xml_writer.into_inner().into_inner())
     }
 
-    fn process_rels_xml(&self, xml_data: &[u8]) -> Result<Vec<u8>, DocxError> {
-        // 创建xml写对象
-        let mut writer = Writer::new(Cursor::new(Vec::new()));
-        // 写入xml标签头
-        writer.write_event(Event::Decl(BytesDecl::new(
-            "1.0",
-            Some("UTF-8"),
-            Some("yes"),
-        )))?;
-
-        // 写入XML根元素
-        writer.write_event(Event::Start(
-            BytesStart::new("Relationships").with_attributes([(
-                "xmlns",
-                "http://schemas.openxmlformats.org/package/2006/relationships",
-            )]),
-        ))?;
-
-        // 读取原始数据
-        let mut reader = quick_xml::Reader::from_reader(xml_data);
-        reader.config_mut().trim_text(true);
-        let mut buf = Vec::new();
-
-        loop {
-            // 读取关系文件
-            match reader.read_event_into(&mut buf)? {
-                // 判断关系文件内容是否为关联标签
-                Event::Empty(e) if e.name().as_ref() == b"Relationship" => {
-                    // 写入关系标签内容
-                    writer.write_event(Event::Empty(e))?;
-                }
-                // 文件读取完毕
-                Event::Eof => break,
-                _ => {}
+    /// 在图片占位符所在段落中写入图片或图片组
+    /// @param writer 写入对象
+    /// @param placeholder 图片占位符
+    /// @param max_width 当前位置可以显示图片的最大宽度（emu）
+    /// @param caption_style 题注样式编号
+    /// @param sizes 记录图片的显示大小
+    /// @param captions 题注编号
+    fn write_placeholder_image<T: Write>(
+        &self,
+        writer: &mut Writer<T>,
+        placeholder: &str,
+        max_width: u64,
+        caption_style: &str,
+        sizes: &mut DisplaySizes,
+        captions: &mut CaptionCounter,
+    ) -> Result<(), DocxError> {
+        if let Some(gallery) = self.galleries.get(placeholder) {
+            // 替换占位符为多张图片
+            write_gallery(writer, gallery, max_width, caption_style, sizes)?;
+        } else if let Some(Some(docx_image)) = self.image_replacements.get(placeholder) {
+            // 替换占位符为图片
+            let (width, height) = image_size(docx_image, max_width);
+            sizes.record(&docx_image.relation_id, width, height);
+            writer_drawing(writer, docx_image, width, height)?;
+            // 图片下方的题注段落
+            if let Some(caption) = &docx_image.caption {
+                write_caption(writer, caption, caption_style, captions)?;
             }
-            // 清理内容
-            buf.clear();
         }
+        Ok(())
+    }
 
-        // 添加新的图片关系
-        for placeholder in self.images_map.values() {
-            if let Some(Some(docx_image)) = self.image_replacements.get(placeholder) {
-                // 创建图片路径
-                let image_path = format!(
-                    "media/image_{}.{}",
-                    docx_image.relation_id, docx_image.image_ext
-                );
-                // 创建图片关系标签
-                let relationship = BytesStart::new("Relationship").with_attributes([
-                    ("Id", docx_image.relation_id.as_str()),
-                    (
-                        "Type",
-                        "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image",
-                    ),
-                    ("Target", &image_path),
-                ]);
-                // 写入关系标签数据
-                writer.write_event(Event::Empty(relationship))?;
-            }
-        }
+    /// 处理关系文件
+    /// @param relationships 模板中原有的关系标签
+    fn process_rels_xml(
+        &self,
+        relationships: &[BytesStart<'static>],
+    ) -> Result<Vec<u8>, DocxError> {
+        writer_rels_xml(relationships, self.images())
+    }
+
+    /// 获取需要写入文档的图片，同一图片只返回一次
+    pub(crate) fn images(&self) -> impl Iterator<Item = &DocxImage> {
+        let mut relation_ids = HashSet::new();
+        self.images_map
+            .values()
+            .filter_map(|placeholder| self.image_replacements.get(placeholder))
+            .flatten()
+            .chain(
+                self.galleries
+                    .values()
+                    .flat_map(|gallery| gallery.images.iter().map(|item| &item.image)),
+            )
+            .filter(move |docx_image| relation_ids.insert(docx_image.relation_id.as_str()))
+    }
 
-        // 结束根元素
-        writer.write_event(Event::End(BytesEnd::new("Relationships")))?;
-        // 输出关系文件内容
-        Ok(writer.into_inner().into_inner())
+    /// 判断是否为图片或图片组占位符
+    /// @param placeholder 替换的字符串
+    fn is_image_placeholder(&self, placeholder: &str) -> bool {
+        self.image_replacements.contains_key(placeholder)
+            || self.galleries.contains_key(placeholder)
     }
 
     // 替换模板属性
+    // 只计算模板中的占位符，替换后的数据原样写入，数据中的{{...}}不会作为表达式计算
     fn process_text(&self, text: &mut String) {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find(PREFIX_TAG) {
+            let Some(end) = rest[start..].find(SUFFIX_TAG) else {
+                break;
+            };
+            let end = start + end + SUFFIX_TAG.len();
+            let placeholder = &rest[start..end];
+            result.push_str(&self.replace_literal(&rest[..start]));
+            // 处理带过滤器或表达式的占位符，例如{{amount|rmb_upper}}、{{qty * price}}，计算失败时保留原占位符
+            match self.text_replacements.get(placeholder) {
+                Some(value) => result.push_str(value),
+                None => match self.expression_value(placeholder) {
+                    Some(value) => result.push_str(&value),
+                    None => result.push_str(placeholder),
+                },
+            }
+            rest = &rest[end..];
+        }
+        result.push_str(&self.replace_literal(rest));
+        *text = result;
+    }
+
+    /// 替换占位符之外的文本中其他格式的替换字符串
+    /// @param text 不包含完整占位符的文本
+    fn replace_literal(&self, text: &str) -> String {
+        let mut text = text.to_string();
         for (placeholder, value) in &self.text_replacements {
-            *text = text.replace(placeholder, value);
+            if text.contains(placeholder.as_str()) {
+                text = text.replace(placeholder, value);
+            }
+        }
+        text
+    }
+
+    /// 计算占位符表达式的值，过滤器按顺序执行
+    /// @param placeholder 占位符，例如{{qty * price|rmb_upper}}
+    fn expression_value(&self, placeholder: &str) -> Option<String> {
+        let inner = &placeholder[PREFIX_TAG.len()..placeholder.len() - SUFFIX_TAG.len()];
+        let mut parts = split_filters(inner).into_iter();
+        let expression = parts.next()?;
+        let lookup = |name: &str| {
+            self.text_replacements
+                .get(&format!("{}{}{}", PREFIX_TAG, name, SUFFIX_TAG))
+                .cloned()
+        };
+        // 单个变量直接使用原始字符串，不经过表达式计算
+        let mut value = match lookup(expression.trim()) {
+            Some(value) => value,
+            None => match evaluate(expression, lookup) {
+                Ok(value) => value.to_string(),
+                Err(e) => {
+                    debug!("{} expression error: {}", placeholder, e);
+                    return None;
+                }
+            },
+        };
+        for filter in parts {
+            match apply_filter(filter, &value) {
+                Ok(formatted) => value = formatted,
+                Err(e) => {
+                    debug!("{} filter error: {}", placeholder, e);
+                    return None;
+                }
+            }
         }
+        Some(value)
     }
 }
 
@@ -483,34 +1090,272 @@ impl Default for DocxTemplate {
     }
 }
 
+/// 生成关系文件内容
+/// @param relationships 模板中原有的关系标签
+/// @param images 新添加的图片
+fn writer_rels_xml<'a>(
+    relationships: &[BytesStart<'static>],
+    images: impl Iterator<Item = &'a DocxImage>,
+) -> Result<Vec<u8>, DocxError> {
+    // 创建xml写对象
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    // 写入xml标签头
+    writer.write_event(Event::Decl(BytesDecl::new(
+        "1.0",
+        Some("UTF-8"),
+        Some("yes"),
+    )))?;
+
+    // 写入XML根元素
+    writer.write_event(Event::Start(
+        BytesStart::new("Relationships").with_attributes([(
+            "xmlns",
+            "http://schemas.openxmlformats.org/package/2006/relationships",
+        )]),
+    ))?;
+
+    // 写入原有的关系标签
+    for relationship in relationships {
+        writer.write_event(Event::Empty(relationship.borrow()))?;
+    }
+
+    // 添加新的图片关系
+    for (relation_id, image_ext, _, _) in images.flat_map(media_files) {
+        // 创建图片路径
+        let image_path = format!("media/image_{}.{}", relation_id, image_ext);
+        // 创建图片关系标签
+        let relationship = BytesStart::new("Relationship").with_attributes([
+            ("Id", relation_id.as_str()),
+            (
+                "Type",
+                "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image",
+            ),
+            ("Target", &image_path),
+        ]);
+        // 写入关系标签数据
+        writer.write_event(Event::Empty(relationship))?;
+    }
+
+    // 结束根元素
+    writer.write_event(Event::End(BytesEnd::new("Relationships")))?;
+    // 输出关系文件内容
+    Ok(writer.into_inner().into_inner())
+}
+
+/// 写入图片标签
+/// @param writer 写入对象
+/// @param docx_image 图片对象
+/// @param width 图片显示宽度（emu）
+/// @param height 图片显示高度（emu）
+fn writer_drawing<T: Write>(
+    writer: &mut Writer<T>,
+    docx_image: &DocxImage,
+    width: u64,
+    height: u64,
+) -> Result<(), DocxError> {
+    let svg_relation_id = docx_image
+        .svg_data
+        .as_ref()
+        .map(|_| docx_image.svg_relation_id());
+    create_drawing_element(
+        writer,
+        &docx_image.relation_id,
+        svg_relation_id.as_deref(),
+        width,
+        height,
+        docx_image.style.crop_rect().or(docx_image.crop),
+        &docx_image.style,
+    )
+}
+
+/// 记录模板中已有图片的显示大小，wp:extent在wp:docPr之前
+/// @param element 标签
+/// @param swap 待替换的图片
+/// @param extent 最近一个图片框的大小
+/// @param sizes 记录图片的显示大小
+fn record_extent(
+    element: &BytesStart,
+    swap: Option<&DocxImage>,
+    extent: &mut Option<(u64, u64)>,
+    sizes: &mut DisplaySizes,
+) {
+    match element.name().as_ref() {
+        b"wp:extent" => {
+            let value = |name: &str| {
+                element
+                    .try_get_attribute(name)
+                    .ok()
+                    .flatten()
+                    .and_then(|a| a.unescape_value().ok())
+                    .and_then(|v| v.parse::<u64>().ok())
+            };
+            *extent = value("cx").zip(value("cy"));
+        }
+        b"wp:docPr" => {
+            if let (Some(docx_image), Some((width, height))) = (swap, *extent) {
+                sizes.record(&docx_image.relation_id, width, height);
+            }
+        }
+        _ => {}
+    }
+}
+
 /// 写入图片  
 /// @param zip_writer 写入对象  
 /// @param replacement 图片对象  
-fn writer_image(
-    zip_writer: &mut ZipWriter<File>,
+/// @param sizes 图片的显示大小，设置了目标DPI时按显示大小缩小图片
+fn writer_image<W: Write + Seek>(
+    zip_writer: &mut ZipWriter<W>,
     replacement: &DocxImage,
+    sizes: &DisplaySizes,
 ) -> Result<(), DocxError> {
-    let image_path = format!(
-        "{}{}.{}",
-        WORD_MEDIA_IMAGE, replacement.relation_id, replacement.image_ext,
-    );
-    // 写入图片到word压缩文件中
-    zip_writer.start_file(&image_path, SimpleFileOptions::default())?;
-    zip_writer.write_all(&replacement.image_data)?;
+    let (width, height) = sizes.get(replacement);
+    let display_data = replacement.display_data(width, height);
+    for (relation_id, image_ext, _, image_data) in media_files(replacement) {
+        let image_path = format!("{}{}.{}", WORD_MEDIA_IMAGE, relation_id, image_ext);
+        let image_data = match &display_data {
+            Some(display_data) if relation_id == replacement.relation_id => display_data,
+            _ => image_data,
+        };
+        // 写入图片到word压缩文件中
+        zip_writer.start_file(&image_path, SimpleFileOptions::default())?;
+        zip_writer.write_all(image_data)?;
+    }
     Ok(())
 }
 
-pub fn writer_file(
-    zip_writer: &mut ZipWriter<File>,
-    file: &ZipFile<File>,
+/// 图片需要写入文档的文件，SVG图片同时写入PNG备用图片
+/// @param docx_image 图片对象
+/// @return (relation_id, image_ext, content_type, image_data) 关联编号、扩展名、内容类型和数据
+fn media_files(docx_image: &DocxImage) -> Vec<(String, &str, &str, &[u8])> {
+    let mut files = vec![(
+        docx_image.relation_id.clone(),
+        docx_image.image_ext.as_str(),
+        docx_image.content_type.as_str(),
+        docx_image.image_data.as_slice(),
+    )];
+    if let Some(svg_data) = &docx_image.svg_data {
+        files.push((
+            docx_image.svg_relation_id(),
+            "svg",
+            "image/svg+xml",
+            svg_data.as_slice(),
+        ));
+    }
+    files
+}
+
+/// 生成内容类型文件，为新添加图片的扩展名补充Default声明，避免文档无法打开
+/// @param events 模板中内容类型文件的xml事件
+/// @param images 新添加的图片
+fn writer_content_types<'a>(
+    events: &[Event<'static>],
+    images: impl Iterator<Item = &'a DocxImage>,
+) -> Result<Vec<u8>, DocxError> {
+    // 模板中已经声明的扩展名
+    let mut extensions: Vec<String> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Empty(e) | Event::Start(e) if e.name().as_ref() == b"Default" => {
+                e.try_get_attribute("Extension").ok().flatten()
+            }
+            _ => None,
+        })
+        .filter_map(|a| a.unescape_value().ok().map(|v| v.to_ascii_lowercase()))
+        .collect();
+    // 需要补充的声明
+    let mut defaults = Vec::new();
+    for (_, image_ext, content_type, _) in images.flat_map(media_files) {
+        if !extensions.iter().any(|ext| ext == image_ext) {
+            extensions.push(image_ext.to_string());
+            defaults.push(
+                BytesStart::new("Default")
+                    .with_attributes([("Extension", image_ext), ("ContentType", content_type)]),
+            );
+        }
+    }
+
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    for event in events {
+        if let Event::End(e) = event {
+            if e.name().as_ref() == b"Types" {
+                for default in defaults.drain(..) {
+                    writer.write_event(Event::Empty(default))?;
+                }
+            }
+        }
+        writer.write_event(event.borrow())?;
+    }
+    Ok(writer.into_inner().into_inner())
+}
+
+/// 内存图片的路径，同一占位符重复添加时覆盖之前的图片
+/// @param placeholder 替换的字符串
+fn memory_image_key(placeholder: &str) -> String {
+    format!("memory:{}", placeholder)
+}
+
+/// 按单个“|”拆分表达式与过滤器，忽略“||”以及字符串中的“|”
+/// @param inner 占位符内容
+fn split_filters(inner: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    let chars: Vec<(usize, char)> = inner.char_indices().collect();
+    for (i, (index, c)) in chars.iter().enumerate() {
+        match (quote, *c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(*c),
+            (None, '“') => quote = Some('”'),
+            (None, '|') => {
+                let prev = i > 0 && chars[i - 1].1 == '|';
+                let next = chars.get(i + 1).is_some_and(|(_, c)| *c == '|');
+                if !prev && !next {
+                    parts.push(&inner[start..*index]);
+                    start = index + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    parts.push(&inner[start..]);
+    parts
+}
+
+pub fn writer_file<W: Write + Seek, R: Read>(
+    zip_writer: &mut ZipWriter<W>,
+    file: &ZipFile<R>,
+    contents: &[u8],
+) -> Result<(), DocxError> {
+    writer_entry(
+        zip_writer,
+        file.name(),
+        file.compression(),
+        file.unix_mode(),
+        contents,
+    )
+}
+
+/// 写入压缩文件条目  
+/// @param zip_writer 写入对象  
+/// @param name 文件名称  
+/// @param compression 压缩方式  
+/// @param unix_mode 文件权限  
+/// @param contents 文件内容  
+fn writer_entry<W: Write + Seek>(
+    zip_writer: &mut ZipWriter<W>,
+    name: &str,
+    compression: CompressionMethod,
+    unix_mode: Option<u32>,
     contents: &[u8],
 ) -> Result<(), DocxError> {
     // 写入新文件
     let option = SimpleFileOptions::default()
-        .compression_method(file.compression())
-        .unix_permissions(file.unix_mode().unwrap_or(0o644));
+        .compression_method(compression)
+        .unix_permissions(unix_mode.unwrap_or(0o644));
     // 写入内容
-    zip_writer.start_file(file.name(), option)?;
+    zip_writer.start_file(name, option)?;
     zip_writer.write_all(contents)?;
 
     Ok(())
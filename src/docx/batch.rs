@@ -0,0 +1,277 @@
+use crate::docx::{CompiledTemplate, DocxTemplate};
+use crate::error::DocxError;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::sync_channel;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// 批量生成的输出位置
+#[derive(Debug, Clone)]
+pub enum BatchOutput {
+    // 输出到目录，每条记录一个docx文件
+    Directory(PathBuf),
+    // 输出到一个zip压缩文件
+    Zip(PathBuf),
+}
+
+/// 批量生成参数
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    // 文件名模板，例如{{id}}_{{name}}.docx
+    pub file_name_pattern: String,
+    // 并发渲染的线程数，小于等于1时顺序渲染
+    pub threads: usize,
+    // 是否覆盖已存在的文件，默认不覆盖，文件已存在时返回错误
+    pub overwrite: bool,
+}
+
+impl BatchOptions {
+    /// 创建批量生成参数
+    /// @param file_name_pattern 文件名模板，支持占位符、表达式和过滤器
+    pub fn new(file_name_pattern: &str) -> Self {
+        BatchOptions {
+            file_name_pattern: file_name_pattern.to_string(),
+            threads: 1,
+            overwrite: false,
+        }
+    }
+
+    /// 设置并发渲染的线程数
+    /// @param threads 线程数
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// 设置是否覆盖输出目录中已存在的文件或已存在的zip文件
+    /// @param overwrite 是否覆盖
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+}
+
+/// 单条记录生成失败的信息
+#[derive(Debug)]
+pub struct BatchError {
+    // 记录序号
+    pub index: usize,
+    // 文件名称
+    pub file_name: String,
+    // 错误信息
+    pub error: DocxError,
+}
+
+/// 批量生成结果
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    // 生成成功的文件名称
+    pub files: Vec<String>,
+    // 生成失败的记录
+    pub errors: Vec<BatchError>,
+}
+
+// 渲染结果：序号、文件名称、文档数据
+type Rendered = (usize, String, Result<Vec<u8>, DocxError>);
+
+impl CompiledTemplate {
+    /// 批量生成文档，单条记录失败不会中断，错误收集在结果中
+    /// @param records 每条记录的替换数据
+    /// @param output 输出位置
+    /// @param options 批量生成参数
+    /// @return 批量生成结果
+    pub fn render_batch<I>(
+        &self,
+        records: I,
+        output: &BatchOutput,
+        options: &BatchOptions,
+    ) -> Result<BatchReport, DocxError>
+    where
+        I: IntoIterator<Item = DocxTemplate>,
+        I::IntoIter: Send,
+    {
+        let mut sink = BatchSink::new(output, options.overwrite)?;
+        let mut report = BatchReport::default();
+
+        if options.threads <= 1 {
+            // 顺序渲染
+            let mut names = HashSet::new();
+            for (index, record) in records.into_iter().enumerate() {
+                let file_name = self.file_name(index, &record, options, &mut names);
+                sink.accept((index, file_name, self.render(&record)), &mut report);
+            }
+        } else {
+            // 多线程渲染，主线程按记录顺序写入，输出与线程数和完成顺序无关
+            // 取记录时同时确定文件名，重名后缀按记录顺序分配
+            let records = Mutex::new((records.into_iter().enumerate(), HashSet::new()));
+            let (sender, receiver) = sync_channel::<Rendered>(options.threads * 2);
+            std::thread::scope(|scope| {
+                for _ in 0..options.threads {
+                    let sender = sender.clone();
+                    let records = &records;
+                    scope.spawn(move || {
+                        loop {
+                            let next = match records.lock() {
+                                Ok(mut records) => {
+                                    let (records, names) = &mut *records;
+                                    records.next().map(|(index, record)| {
+                                        let file_name =
+                                            self.file_name(index, &record, options, names);
+                                        (index, file_name, record)
+                                    })
+                                }
+                                Err(_) => None,
+                            };
+                            let Some((index, file_name, record)) = next else {
+                                break;
+                            };
+                            if sender
+                                .send((index, file_name, self.render(&record)))
+                                .is_err()
+                            {
+                                break;
+                            }
+                        }
+                    });
+                }
+                drop(sender);
+                // 先完成的后续记录暂存，等待前面的记录
+                let mut pending = BTreeMap::new();
+                let mut next = 0;
+                for rendered in receiver {
+                    pending.insert(rendered.0, rendered);
+                    while let Some(rendered) = pending.remove(&next) {
+                        sink.accept(rendered, &mut report);
+                        next += 1;
+                    }
+                }
+            });
+        }
+
+        sink.finish()?;
+        Ok(report)
+    }
+
+    /// 生成单条记录的文件名并登记，文件名重复时追加后缀
+    fn file_name(
+        &self,
+        index: usize,
+        record: &DocxTemplate,
+        options: &BatchOptions,
+        names: &mut HashSet<String>,
+    ) -> String {
+        let mut file_name = options.file_name_pattern.clone();
+        record.process_text(&mut file_name);
+        unique_file_name(names, sanitize_file_name(&file_name, index), index)
+    }
+}
+
+// 批量生成的写入对象
+enum BatchSink {
+    // 输出目录，是否覆盖已存在的文件
+    Directory(PathBuf, bool),
+    Zip(Box<ZipWriter<File>>),
+}
+
+impl BatchSink {
+    fn new(output: &BatchOutput, overwrite: bool) -> Result<Self, DocxError> {
+        match output {
+            BatchOutput::Directory(path) => {
+                std::fs::create_dir_all(path)?;
+                Ok(BatchSink::Directory(path.clone(), overwrite))
+            }
+            BatchOutput::Zip(path) => {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let zip_writer = ZipWriter::new(create_file(path, overwrite)?);
+                Ok(BatchSink::Zip(Box::new(zip_writer)))
+            }
+        }
+    }
+
+    /// 写入单条渲染结果
+    fn accept(&mut self, rendered: Rendered, report: &mut BatchReport) {
+        let (index, file_name, result) = rendered;
+        let result = result.and_then(|contents| self.write(&file_name, &contents));
+        match result {
+            Ok(()) => report.files.push(file_name),
+            Err(error) => report.errors.push(BatchError {
+                index,
+                file_name,
+                error,
+            }),
+        }
+    }
+
+    fn write(&mut self, file_name: &str, contents: &[u8]) -> Result<(), DocxError> {
+        match self {
+            BatchSink::Directory(path, overwrite) => {
+                create_file(&path.join(file_name), *overwrite)?.write_all(contents)?;
+            }
+            BatchSink::Zip(zip_writer) => {
+                // docx本身已压缩，直接存储
+                let option =
+                    SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+                zip_writer.start_file(file_name, option)?;
+                zip_writer.write_all(contents)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), DocxError> {
+        if let BatchSink::Zip(zip_writer) = self {
+            zip_writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// 创建输出文件，不覆盖时文件已存在返回错误
+/// @param path 文件路径
+/// @param overwrite 是否覆盖已存在的文件
+fn create_file(path: &Path, overwrite: bool) -> Result<File, DocxError> {
+    let file = match overwrite {
+        true => File::create(path)?,
+        false => File::create_new(path)?,
+    };
+    Ok(file)
+}
+
+/// 去掉文件名中不允许的字符，文件名为空时使用记录序号
+fn sanitize_file_name(file_name: &str, index: usize) -> String {
+    let file_name: String = file_name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if file_name.is_empty() || file_name.starts_with('.') {
+        format!("{}{}", index + 1, file_name)
+    } else {
+        file_name
+    }
+}
+
+/// 文件名重复时追加记录序号，追加后仍重复时继续递增序号
+fn unique_file_name(names: &mut HashSet<String>, file_name: String, index: usize) -> String {
+    let mut unique = file_name.clone();
+    let mut suffix = index + 1;
+    while names.contains(&unique) {
+        unique = match file_name.rsplit_once('.') {
+            Some((stem, ext)) => format!("{}_{}.{}", stem, suffix, ext),
+            None => format!("{}_{}", file_name, suffix),
+        };
+        suffix += 1;
+    }
+    names.insert(unique.clone());
+    unique
+}
@@ -0,0 +1,158 @@
+use crate::caption::ImageCaption;
+use crate::docx::gallery::write_text_run;
+use crate::docx::template::write_xml;
+use crate::error::DocxError;
+use quick_xml::Writer;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// 题注样式的默认编号
+pub(super) static DEFAULT_CAPTION_STYLE: &str = "Caption";
+
+/// 文档中每个SEQ域序列的编号，用于生成题注编号的显示值，打开文档更新域后以Word计算的为准
+#[derive(Default)]
+pub(super) struct CaptionCounter {
+    numbers: HashMap<String, u32>,
+    // 是否在域代码标签w:instrText中
+    instruction: bool,
+}
+
+impl CaptionCounter {
+    /// 读取模板中已有的SEQ域，只处理w:instrText中的域代码和w:fldSimple的w:instr属性，
+    /// 需要按顺序处理文档中的每个事件
+    /// @param event 文档事件
+    pub(super) fn update(&mut self, event: &Event) {
+        match event {
+            Event::Start(e) if e.name().as_ref() == b"w:instrText" => self.instruction = true,
+            Event::End(e) if e.name().as_ref() == b"w:instrText" => self.instruction = false,
+            Event::Text(e) if self.instruction => {
+                if let Ok(text) = e.unescape() {
+                    self.read(&text);
+                }
+            }
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"w:fldSimple" => {
+                let instruction = e
+                    .try_get_attribute("w:instr")
+                    .ok()
+                    .flatten()
+                    .and_then(|a| a.unescape_value().ok());
+                if let Some(instruction) = instruction {
+                    self.read(&instruction);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 读取域代码，例如 SEQ Figure \* ARABIC
+    /// @param instruction 域代码
+    fn read(&mut self, instruction: &str) {
+        let mut parts = instruction.split_whitespace();
+        if let (Some("SEQ"), Some(label)) = (parts.next(), parts.next()) {
+            self.next(label);
+        }
+    }
+
+    /// 获取序列的下一个编号
+    /// @param label 序列名称
+    fn next(&mut self, label: &str) -> u32 {
+        let number = self.numbers.entry(label.to_string()).or_insert(0);
+        *number += 1;
+        *number
+    }
+}
+
+/// 在图片所在段落之后写入题注段落，由调用方写入题注段落的结束标签
+/// @param writer 写入对象
+/// @param caption 题注
+/// @param caption_style 题注样式编号
+/// @param counter 题注编号
+pub(super) fn write_caption<T: Write>(
+    writer: &mut Writer<T>,
+    caption: &ImageCaption,
+    caption_style: &str,
+    counter: &mut CaptionCounter,
+) -> Result<(), DocxError> {
+    writer.write_event(Event::End(BytesEnd::new("w:p")))?;
+    writer.write_event(Event::Start(BytesStart::new("w:p")))?;
+    write_caption_style(writer, caption_style, false)?;
+    write_text_run(writer, &format!("{} ", caption.label), "")?;
+    // SEQ域：开始、域代码、分隔、显示值、结束
+    let field_char =
+        |field_type: &str| format!(r#"<w:r><w:fldChar w:fldCharType="{}"/></w:r>"#, field_type);
+    write_xml(writer, &field_char("begin"))?;
+    writer.write_event(Event::Start(BytesStart::new("w:r")))?;
+    writer.write_event(Event::Start(
+        BytesStart::new("w:instrText").with_attributes([("xml:space", "preserve")]),
+    ))?;
+    writer.write_event(Event::Text(BytesText::new(&format!(
+        " SEQ {} \\* ARABIC ",
+        caption.label
+    ))))?;
+    writer.write_event(Event::End(BytesEnd::new("w:instrText")))?;
+    writer.write_event(Event::End(BytesEnd::new("w:r")))?;
+    write_xml(writer, &field_char("separate"))?;
+    write_text_run(writer, &counter.next(&caption.label).to_string(), "")?;
+    write_xml(writer, &field_char("end"))?;
+    if !caption.text.is_empty() {
+        write_text_run(writer, &format!(" {}", caption.text), "")?;
+    }
+    Ok(())
+}
+
+/// 写入题注样式的段落属性
+/// @param writer 写入对象
+/// @param caption_style 题注样式编号
+/// @param center 是否居中
+pub(super) fn write_caption_style<T: Write>(
+    writer: &mut Writer<T>,
+    caption_style: &str,
+    center: bool,
+) -> Result<(), DocxError> {
+    writer.write_event(Event::Start(BytesStart::new("w:pPr")))?;
+    writer.write_event(Event::Empty(
+        BytesStart::new("w:pStyle").with_attributes([Attribute::from(("w:val", caption_style))]),
+    ))?;
+    if center {
+        write_xml(writer, r#"<w:jc w:val="center"/>"#)?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("w:pPr")))?;
+    Ok(())
+}
+
+/// 读取样式文件中题注样式的编号，中文版Word创建的模板中编号可能不是Caption
+/// @param contents 样式文件内容
+pub(super) fn parse_caption_style(contents: &[u8]) -> Result<Option<String>, DocxError> {
+    let mut reader = quick_xml::Reader::from_reader(contents);
+    let mut buf = Vec::new();
+    let mut style_id = None;
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) if e.name().as_ref() == b"w:style" => {
+                style_id = e
+                    .try_get_attribute("w:styleId")
+                    .ok()
+                    .flatten()
+                    .and_then(|a| a.unescape_value().ok())
+                    .map(|v| v.into_owned());
+            }
+            Event::Empty(e) if e.name().as_ref() == b"w:name" => {
+                let is_caption = e
+                    .try_get_attribute("w:val")
+                    .ok()
+                    .flatten()
+                    .and_then(|a| a.unescape_value().ok())
+                    .is_some_and(|v| v.eq_ignore_ascii_case("caption"));
+                if is_caption && style_id.is_some() {
+                    return Ok(style_id);
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"w:style" => style_id = None,
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+        buf.clear();
+    }
+}
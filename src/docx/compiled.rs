@@ -0,0 +1,257 @@
+use crate::docx::caption::{CaptionCounter, DEFAULT_CAPTION_STYLE, parse_caption_style};
+use crate::docx::layout::{DisplaySizes, PageLayout};
+use crate::docx::word::*;
+use crate::docx::{
+    DocxTemplate, PREFIX_TAG, SUFFIX_TAG, writer_content_types, writer_entry, writer_image,
+};
+use crate::error::DocxError;
+use quick_xml::events::{BytesStart, Event};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, Write};
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// 预解析的模板，模板文件只读取和解析一次，可以在多个线程中并发渲染
+pub struct CompiledTemplate {
+    // 模板中的文件
+    pub(super) entries: Vec<TemplateEntry>,
+    // 题注样式编号
+    pub(super) caption_style: String,
+}
+
+// 模板中的文件
+pub(super) struct TemplateEntry {
+    // 文件名称
+    pub(super) name: String,
+    // 压缩方式
+    pub(super) compression: CompressionMethod,
+    // 文件权限
+    pub(super) unix_mode: Option<u32>,
+    // 文件内容
+    pub(super) part: TemplatePart,
+}
+
+// 模板文件内容
+pub(super) enum TemplatePart {
+    // 文档主内容，预解析的xml事件、版面信息和占位符位置
+    Document(CompiledDocument),
+    // 关系文件，预解析的关系标签
+    Relationships(Vec<BytesStart<'static>>),
+    // 内容类型文件，预解析的xml事件
+    ContentTypes(Vec<Event<'static>>),
+    // 其他文件，原样写入
+    Raw(Vec<u8>),
+}
+
+// 预解析的文档主内容
+pub(super) struct CompiledDocument {
+    // xml事件
+    pub(super) events: Vec<Event<'static>>,
+    // 每一节的页面宽度，用于限制默认大小图片的宽度
+    pub(super) layout: PageLayout,
+    // 包含占位符的文本，按事件序号排列
+    pub(super) spans: Vec<PlaceholderSpan>,
+}
+
+// 包含占位符的文本，占位符被拆分到多个文本块时合并为一个
+pub(super) struct PlaceholderSpan {
+    // 第一个文本事件序号
+    pub(super) start: usize,
+    // 最后一个文本事件序号，合并后的文本在此位置写入
+    pub(super) end: usize,
+    // 合并后的文本
+    pub(super) text: String,
+}
+
+impl CompiledDocument {
+    /// 解析文档主内容，计算版面信息和占位符位置
+    /// @param contents 文件内容数组
+    fn new(contents: &[u8]) -> Result<Self, DocxError> {
+        let events = parse_events(contents)?;
+        let layout = PageLayout::new(&events);
+        let mut spans = Vec::new();
+        let mut current: Option<PlaceholderSpan> = None;
+        for (index, event) in events.iter().enumerate() {
+            let Event::Text(e) = event else {
+                continue;
+            };
+            let text = e.unescape()?;
+            let span = match current.take() {
+                Some(mut span) => {
+                    span.text.push_str(&text);
+                    span.end = index;
+                    span
+                }
+                None if text.contains(PREFIX_TAG) => PlaceholderSpan {
+                    start: index,
+                    end: index,
+                    text: text.into_owned(),
+                },
+                None => continue,
+            };
+            // 最后一个占位符已经结束时完成合并，否则继续合并后面的文本
+            let complete = span
+                .text
+                .rfind(PREFIX_TAG)
+                .is_some_and(|start| span.text[start..].contains(SUFFIX_TAG));
+            if complete {
+                spans.push(span);
+            } else {
+                current = Some(span);
+            }
+        }
+        // 没有结束标记的占位符按普通文本处理
+        Ok(CompiledDocument {
+            events,
+            layout,
+            spans,
+        })
+    }
+}
+
+impl CompiledTemplate {
+    /// 读取并解析模板文件
+    /// @param template_path 模板路径
+    pub fn load(template_path: &str) -> Result<Self, DocxError> {
+        Self::from_reader(File::open(template_path)?)
+    }
+
+    /// 从内存数据读取并解析模板
+    /// @param template_data 模板文件数据
+    pub fn from_bytes(template_data: &[u8]) -> Result<Self, DocxError> {
+        Self::from_reader(Cursor::new(template_data))
+    }
+
+    /// 读取并解析模板
+    /// @param reader 模板文件读取对象
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self, DocxError> {
+        let mut archive = ZipArchive::new(reader)?;
+        let mut entries = Vec::with_capacity(archive.len());
+        let mut caption_style = None;
+        // 遍历ZIP中的文件
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            // 读取文件内容到数组中
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            if file.name() == WORD_STYLES {
+                caption_style = parse_caption_style(&contents)?;
+            }
+            // 匹配文件类型
+            let part = match file.name() {
+                x if x == WORD_DOCUMENT => {
+                    TemplatePart::Document(CompiledDocument::new(&contents)?)
+                }
+                x if x == CONTENT_TYPES => TemplatePart::ContentTypes(parse_events(&contents)?),
+                x if x == WORD_RELS_DOCUMENT => {
+                    TemplatePart::Relationships(parse_relationships(&contents)?)
+                }
+                _ => TemplatePart::Raw(contents),
+            };
+            entries.push(TemplateEntry {
+                name: file.name().to_string(),
+                compression: file.compression(),
+                unix_mode: file.unix_mode(),
+                part,
+            });
+        }
+        Ok(CompiledTemplate {
+            entries,
+            caption_style: caption_style.unwrap_or_else(|| DEFAULT_CAPTION_STYLE.to_string()),
+        })
+    }
+
+    /// 使用替换数据渲染文档
+    /// @param data 替换数据
+    /// @return 生成的docx文件数据
+    pub fn render(&self, data: &DocxTemplate) -> Result<Vec<u8>, DocxError> {
+        let mut output = Cursor::new(Vec::new());
+        self.render_to(data, &mut output)?;
+        Ok(output.into_inner())
+    }
+
+    /// 使用替换数据渲染文档并写入输出对象
+    /// @param data 替换数据
+    /// @param writer 输出对象
+    pub fn render_to<W: Write + Seek>(
+        &self,
+        data: &DocxTemplate,
+        writer: W,
+    ) -> Result<(), DocxError> {
+        let mut zip_writer = ZipWriter::new(writer);
+        // 图片的显示大小
+        let mut sizes = DisplaySizes::default();
+        for entry in &self.entries {
+            let contents: Cow<[u8]> = match &entry.part {
+                // 处理文档主内容,替换模板内容
+                TemplatePart::Document(document) => Cow::Owned(data.process_document_xml(
+                    document,
+                    &self.caption_style,
+                    &mut sizes,
+                    &mut CaptionCounter::default(),
+                )?),
+                // 处理关系文件
+                TemplatePart::Relationships(relationships) => {
+                    Cow::Owned(data.process_rels_xml(relationships)?)
+                }
+                // 补充新图片类型的内容类型声明
+                TemplatePart::ContentTypes(events) => {
+                    Cow::Owned(writer_content_types(events, data.images())?)
+                }
+                TemplatePart::Raw(contents) => Cow::Borrowed(contents),
+            };
+            writer_entry(
+                &mut zip_writer,
+                &entry.name,
+                entry.compression,
+                entry.unix_mode,
+                &contents,
+            )?;
+        }
+
+        // 添加新的图片文件
+        for replacement in data.images() {
+            writer_image(&mut zip_writer, replacement, &sizes)?;
+        }
+        // 将内容写入压缩文件（docx）
+        zip_writer.finish()?;
+        Ok(())
+    }
+}
+
+/// 解析xml文件为xml事件
+/// @param contents 文件内容数组
+fn parse_events(contents: &[u8]) -> Result<Vec<Event<'static>>, DocxError> {
+    let mut reader = quick_xml::Reader::from_reader(contents);
+    let mut buf = Vec::new();
+    let mut events = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Eof => break,
+            e => events.push(e.into_owned()),
+        }
+        buf.clear();
+    }
+    Ok(events)
+}
+
+/// 解析关系文件中的关系标签
+/// @param contents 文件内容数组
+fn parse_relationships(contents: &[u8]) -> Result<Vec<BytesStart<'static>>, DocxError> {
+    let mut reader = quick_xml::Reader::from_reader(contents);
+    reader.config_mut().trim_text(true);
+    let mut buf = Vec::new();
+    let mut relationships = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            // 判断关系文件内容是否为关联标签
+            Event::Empty(e) if e.name().as_ref() == b"Relationship" => {
+                relationships.push(e.into_owned());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(relationships)
+}
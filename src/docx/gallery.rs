@@ -0,0 +1,129 @@
+use crate::docx::caption::write_caption_style;
+use crate::docx::layout::{CELL_MARGIN_TWIPS, DisplaySizes, TWIP_EMU, fit_width};
+use crate::docx::template::write_xml;
+use crate::docx::writer_drawing;
+use crate::error::DocxError;
+use crate::gallery::{GalleryLayout, ImageGallery};
+use crate::image::DocxImage;
+use quick_xml::Writer;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use std::io::Write;
+
+/// 写入图片组，图片宽度超过页面或单元格宽度时等比例缩小
+/// 表格不能放在段落中，表格排列时先结束占位符所在段落，写入表格后再开始一个空段落
+/// @param writer 写入对象
+/// @param gallery 图片组
+/// @param max_width 当前位置可以显示图片的最大宽度（emu）
+/// @param caption_style 图片说明使用的题注样式编号
+/// @param sizes 记录图片的显示大小
+pub(super) fn write_gallery<T: Write>(
+    writer: &mut Writer<T>,
+    gallery: &ImageGallery,
+    max_width: u64,
+    caption_style: &str,
+    sizes: &mut DisplaySizes,
+) -> Result<(), DocxError> {
+    match gallery.layout {
+        GalleryLayout::Inline(spacing) => {
+            // 用加宽的空格作为图片间距
+            let spacing = format!(
+                r#"<w:rPr><w:spacing w:val="{}"/></w:rPr>"#,
+                spacing.to_emu() / TWIP_EMU
+            );
+            for (index, item) in gallery.images.iter().enumerate() {
+                if index > 0 {
+                    write_text_run(writer, " ", &spacing)?;
+                }
+                write_image_run(writer, &item.image, max_width, sizes)?;
+            }
+        }
+        GalleryLayout::Grid(columns) => {
+            let columns = columns.max(1);
+            let column_twips = max_width / TWIP_EMU / columns as u64;
+            let image_width = column_twips.saturating_sub(CELL_MARGIN_TWIPS) * TWIP_EMU;
+            let cell_pr = format!(
+                r#"<w:tcPr><w:tcW w:w="{}" w:type="dxa"/></w:tcPr>"#,
+                column_twips
+            );
+            let center = r#"<w:pPr><w:jc w:val="center"/></w:pPr>"#;
+
+            writer.write_event(Event::End(BytesEnd::new("w:p")))?;
+            writer.write_event(Event::Start(BytesStart::new("w:tbl")))?;
+            write_xml(
+                writer,
+                &format!(
+                    r#"<w:tblPr><w:tblW w:w="{}" w:type="dxa"/><w:tblLayout w:type="fixed"/></w:tblPr><w:tblGrid>{}</w:tblGrid>"#,
+                    column_twips * columns as u64,
+                    format!(r#"<w:gridCol w:w="{}"/>"#, column_twips).repeat(columns)
+                ),
+            )?;
+            for row in gallery.images.chunks(columns) {
+                writer.write_event(Event::Start(BytesStart::new("w:tr")))?;
+                for item in row {
+                    writer.write_event(Event::Start(BytesStart::new("w:tc")))?;
+                    write_xml(writer, &cell_pr)?;
+                    writer.write_event(Event::Start(BytesStart::new("w:p")))?;
+                    write_xml(writer, center)?;
+                    write_image_run(writer, &item.image, image_width, sizes)?;
+                    writer.write_event(Event::End(BytesEnd::new("w:p")))?;
+                    if let Some(caption) = &item.caption {
+                        writer.write_event(Event::Start(BytesStart::new("w:p")))?;
+                        write_caption_style(writer, caption_style, true)?;
+                        write_text_run(writer, caption, "")?;
+                        writer.write_event(Event::End(BytesEnd::new("w:p")))?;
+                    }
+                    writer.write_event(Event::End(BytesEnd::new("w:tc")))?;
+                }
+                // 最后一行不足的列补充空单元格
+                for _ in row.len()..columns {
+                    write_xml(writer, &format!("<w:tc>{}<w:p/></w:tc>", cell_pr))?;
+                }
+                writer.write_event(Event::End(BytesEnd::new("w:tr")))?;
+            }
+            writer.write_event(Event::End(BytesEnd::new("w:tbl")))?;
+            writer.write_event(Event::Start(BytesStart::new("w:p")))?;
+        }
+    }
+    Ok(())
+}
+
+/// 写入包含图片的文本块
+/// @param writer 写入对象
+/// @param docx_image 图片对象
+/// @param max_width 图片最大宽度（emu）
+/// @param sizes 记录图片的显示大小
+fn write_image_run<T: Write>(
+    writer: &mut Writer<T>,
+    docx_image: &DocxImage,
+    max_width: u64,
+    sizes: &mut DisplaySizes,
+) -> Result<(), DocxError> {
+    let (width, height) = fit_width(docx_image, max_width);
+    sizes.record(&docx_image.relation_id, width, height);
+    writer.write_event(Event::Start(BytesStart::new("w:r")))?;
+    writer_drawing(writer, docx_image, width, height)?;
+    writer.write_event(Event::End(BytesEnd::new("w:r")))?;
+    Ok(())
+}
+
+/// 写入文本块，保留文本中的空格
+/// @param writer 写入对象
+/// @param text 文本
+/// @param run_pr 文本块属性标签
+pub(super) fn write_text_run<T: Write>(
+    writer: &mut Writer<T>,
+    text: &str,
+    run_pr: &str,
+) -> Result<(), DocxError> {
+    writer.write_event(Event::Start(BytesStart::new("w:r")))?;
+    if !run_pr.is_empty() {
+        write_xml(writer, run_pr)?;
+    }
+    writer.write_event(Event::Start(
+        BytesStart::new("w:t").with_attributes([("xml:space", "preserve")]),
+    ))?;
+    writer.write_event(Event::Text(BytesText::new(text)))?;
+    writer.write_event(Event::End(BytesEnd::new("w:t")))?;
+    writer.write_event(Event::End(BytesEnd::new("w:r")))?;
+    Ok(())
+}
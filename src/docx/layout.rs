@@ -0,0 +1,189 @@
+use crate::image::{DOCX_MAX_EMU, DocxImage};
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+
+// 1缇（twip）=635EMU
+pub(super) static TWIP_EMU: u64 = 635;
+// 单元格默认左右边距之和（缇）
+pub(super) static CELL_MARGIN_TWIPS: u64 = 216;
+
+/// 文档的版面信息，用于计算默认大小图片的最大宽度
+#[derive(Clone)]
+pub(super) struct PageLayout {
+    // 每一节结束位置的事件序号和可用宽度（emu）
+    sections: Vec<(usize, u64)>,
+    // 当前所在的单元格宽度，嵌套表格时有多个
+    cells: Vec<Option<u64>>,
+}
+
+impl PageLayout {
+    /// 读取文档中每一节的页面宽度和左右页边距
+    /// @param events 文档内容
+    pub(super) fn new(events: &[Event<'static>]) -> Self {
+        let mut sections = Vec::new();
+        let mut section: Option<SectionSize> = None;
+        for (index, event) in events.iter().enumerate() {
+            match event {
+                Event::Start(e) if e.name().as_ref() == b"w:sectPr" => {
+                    section = Some(SectionSize::default());
+                }
+                Event::Empty(e) if e.name().as_ref() == b"w:sectPr" => {
+                    sections.push((index, SectionSize::default().usable_width()));
+                }
+                Event::Empty(e) | Event::Start(e) => {
+                    if let Some(section) = section.as_mut() {
+                        section.read(e);
+                    }
+                }
+                Event::End(e) if e.name().as_ref() == b"w:sectPr" => {
+                    if let Some(section) = section.take() {
+                        sections.push((index, section.usable_width()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        PageLayout {
+            sections,
+            cells: Vec::new(),
+        }
+    }
+
+    /// 读取单元格宽度，需要按顺序处理文档中的每个事件
+    /// @param event 文档事件
+    pub(super) fn update(&mut self, event: &Event) {
+        match event {
+            Event::Start(e) if e.name().as_ref() == b"w:tc" => self.cells.push(None),
+            Event::End(e) if e.name().as_ref() == b"w:tc" => {
+                self.cells.pop();
+            }
+            Event::Empty(e) | Event::Start(e) if e.name().as_ref() == b"w:tcW" => {
+                // 只处理以缇为单位的宽度，百分比等宽度使用外层宽度
+                let dxa = attribute(e, b"w:type").is_none_or(|t| t == "dxa");
+                let width = attribute(e, b"w:w").and_then(|w| w.parse::<u64>().ok());
+                if let (true, Some(width), Some(cell)) = (dxa, width, self.cells.last_mut()) {
+                    *cell = Some(width.saturating_sub(CELL_MARGIN_TWIPS) * TWIP_EMU);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 获取当前位置可以显示图片的最大宽度
+    /// @param index 当前事件序号
+    pub(super) fn max_width(&self, index: usize) -> u64 {
+        if let Some(width) = self.cells.iter().rev().find_map(|cell| *cell) {
+            return width;
+        }
+        self.sections
+            .iter()
+            .find(|(end, _)| *end >= index)
+            .or(self.sections.last())
+            .map(|(_, width)| *width)
+            .unwrap_or(DOCX_MAX_EMU)
+    }
+}
+
+/// 计算图片显示大小，默认大小的图片超过最大宽度时等比例缩小
+/// @param docx_image 图片对象
+/// @param max_width 当前位置可以显示图片的最大宽度（emu）
+pub(super) fn image_size(docx_image: &DocxImage, max_width: u64) -> (u64, u64) {
+    if docx_image.auto_size {
+        fit_width(docx_image, max_width)
+    } else {
+        docx_image.cropped_size()
+    }
+}
+
+/// 图片宽度超过最大宽度时等比例缩小
+/// @param docx_image 图片对象
+/// @param max_width 最大宽度（emu）
+pub(super) fn fit_width(docx_image: &DocxImage, max_width: u64) -> (u64, u64) {
+    let (width, height) = docx_image.cropped_size();
+    if width > max_width && max_width > 0 {
+        let height = (height as f64 * max_width as f64 / width as f64) as u64;
+        (max_width, height)
+    } else {
+        (width, height)
+    }
+}
+
+/// 图片在文档中的最大显示大小，写入图片时按显示大小和目标DPI缩小图片
+#[derive(Default)]
+pub(super) struct DisplaySizes(HashMap<String, (u64, u64)>);
+
+impl DisplaySizes {
+    /// 记录图片的显示大小，同一图片多次显示时取最大值
+    /// @param relation_id 图片关联编号
+    /// @param width 显示宽度（emu）
+    /// @param height 显示高度（emu）
+    pub(super) fn record(&mut self, relation_id: &str, width: u64, height: u64) {
+        let size = self
+            .0
+            .entry(relation_id.to_string())
+            .or_insert((width, height));
+        *size = (size.0.max(width), size.1.max(height));
+    }
+
+    /// 合并另一份文档中的显示大小，关联编号按映射关系替换
+    /// @param other 另一份文档中的显示大小
+    /// @param relation_map 关联编号的映射关系
+    pub(super) fn merge(&mut self, other: DisplaySizes, relation_map: &HashMap<String, String>) {
+        for (relation_id, (width, height)) in other.0 {
+            let relation_id = relation_map.get(&relation_id).unwrap_or(&relation_id);
+            self.record(relation_id, width, height);
+        }
+    }
+
+    /// 获取图片的显示大小，文档中没有显示的图片使用图片本身的大小
+    /// @param docx_image 图片对象
+    pub(super) fn get(&self, docx_image: &DocxImage) -> (u64, u64) {
+        self.0
+            .get(&docx_image.relation_id)
+            .copied()
+            .unwrap_or((docx_image.width, docx_image.height))
+    }
+}
+
+// 一节的页面宽度和左右页边距（缇）
+#[derive(Default)]
+struct SectionSize {
+    width: Option<u64>,
+    left: Option<u64>,
+    right: Option<u64>,
+}
+
+impl SectionSize {
+    fn read(&mut self, element: &BytesStart) {
+        let value = |name: &[u8]| attribute(element, name).and_then(|v| v.parse::<u64>().ok());
+        match element.name().as_ref() {
+            b"w:pgSz" => self.width = value(b"w:w"),
+            b"w:pgMar" => {
+                self.left = value(b"w:left");
+                self.right = value(b"w:right");
+            }
+            _ => {}
+        }
+    }
+
+    /// 页面宽度减去左右页边距，没有页面设置时使用半个A4纸宽度
+    fn usable_width(&self) -> u64 {
+        match self.width {
+            Some(width) => {
+                let margin = self.left.unwrap_or(0) + self.right.unwrap_or(0);
+                width.saturating_sub(margin) * TWIP_EMU
+            }
+            None => DOCX_MAX_EMU,
+        }
+    }
+}
+
+/// 读取标签属性
+fn attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
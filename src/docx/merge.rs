@@ -0,0 +1,438 @@
+use crate::docx::caption::CaptionCounter;
+use crate::docx::compiled::TemplatePart;
+use crate::docx::layout::DisplaySizes;
+use crate::docx::{
+    CompiledTemplate, DocxTemplate, writer_content_types, writer_entry, writer_image,
+    writer_rels_xml,
+};
+use crate::error::DocxError;
+use crate::image::DocxImage;
+use quick_xml::Writer;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Seek, Write};
+use uuid::Uuid;
+use zip::ZipWriter;
+
+/// 合并文档时每份内容之间的分隔方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeSeparator {
+    // 分页符
+    PageBreak,
+    // 分节符（下一页），沿用模板的页面设置
+    SectionBreak,
+}
+
+impl CompiledTemplate {
+    /// 将多条记录渲染并合并为一个文档
+    /// @param records 每条记录的替换数据
+    /// @param separator 每份内容之间的分隔方式
+    /// @return 生成的docx文件数据
+    pub fn render_merged<'a, I>(
+        &self,
+        records: I,
+        separator: MergeSeparator,
+    ) -> Result<Vec<u8>, DocxError>
+    where
+        I: IntoIterator<Item = &'a DocxTemplate>,
+    {
+        let mut output = Cursor::new(Vec::new());
+        self.render_merged_to(records, separator, &mut output)?;
+        Ok(output.into_inner())
+    }
+
+    /// 将多条记录渲染并合并为一个文档，写入输出对象
+    /// @param records 每条记录的替换数据
+    /// @param separator 每份内容之间的分隔方式
+    /// @param writer 输出对象
+    pub fn render_merged_to<'a, I, W>(
+        &self,
+        records: I,
+        separator: MergeSeparator,
+        writer: W,
+    ) -> Result<(), DocxError>
+    where
+        I: IntoIterator<Item = &'a DocxTemplate>,
+        W: Write + Seek,
+    {
+        let document = self
+            .entries
+            .iter()
+            .find_map(|entry| match &entry.part {
+                TemplatePart::Document(document) => Some(document),
+                _ => None,
+            })
+            .ok_or_else(|| DocxError::InvalidTemplate("word/document.xml not found".into()))?;
+
+        // 1. 逐条渲染并合并文档内容
+        let mut merged = MergedDocument::new(separator);
+        let mut images: Vec<DocxImage> = Vec::new();
+        let mut image_ids: HashSet<String> = HashSet::new();
+        let mut image_hashes: HashMap<[u8; 32], usize> = HashMap::new();
+        let mut sizes = DisplaySizes::default();
+        // 所有记录的题注连续编号
+        let mut captions = CaptionCounter::default();
+        for record in records {
+            let mut relation_map = HashMap::new();
+            for image in record.images() {
+                let hash = image.content_hash();
+                // 内容相同的图片共用已合并图片的关联编号
+                if let Some(index) = image_hashes.get(&hash) {
+                    let merged_image = &images[*index];
+                    if merged_image.relation_id != image.relation_id {
+                        relation_map
+                            .insert(image.relation_id.clone(), merged_image.relation_id.clone());
+                        relation_map
+                            .insert(image.svg_relation_id(), merged_image.svg_relation_id());
+                    }
+                    continue;
+                }
+                let mut image = image.clone();
+                // 图片关系编号与已合并的其他图片冲突时重新编号
+                if image_ids.contains(&image.relation_id) {
+                    let relation_id = format!("rId{}", Uuid::new_v4().simple());
+                    relation_map.insert(image.relation_id.clone(), relation_id.clone());
+                    let svg_relation_id = image.svg_relation_id();
+                    image.relation_id = relation_id;
+                    // SVG图片的关联编号随PNG备用图片一起变化
+                    if image.svg_data.is_some() {
+                        relation_map.insert(svg_relation_id, image.svg_relation_id());
+                    }
+                }
+                image_ids.insert(image.relation_id.clone());
+                image_hashes.insert(hash, images.len());
+                images.push(image);
+            }
+            let mut record_sizes = DisplaySizes::default();
+            let contents = record.process_document_xml(
+                document,
+                &self.caption_style,
+                &mut record_sizes,
+                &mut captions,
+            )?;
+            merged.append(&contents, &relation_map)?;
+            sizes.merge(record_sizes, &relation_map);
+        }
+        if merged.copies == 0 {
+            return Err(DocxError::InvalidTemplate("no records".into()));
+        }
+
+        // 2. 写入合并后的文件
+        let mut zip_writer = ZipWriter::new(writer);
+        for entry in &self.entries {
+            let contents: Cow<[u8]> = match &entry.part {
+                TemplatePart::Document(_) => Cow::Owned(merged.finish()?),
+                TemplatePart::Relationships(relationships) => {
+                    Cow::Owned(writer_rels_xml(relationships, images.iter())?)
+                }
+                TemplatePart::ContentTypes(events) => {
+                    Cow::Owned(writer_content_types(events, images.iter())?)
+                }
+                TemplatePart::Raw(contents) => Cow::Borrowed(contents),
+            };
+            writer_entry(
+                &mut zip_writer,
+                &entry.name,
+                entry.compression,
+                entry.unix_mode,
+                &contents,
+            )?;
+        }
+        // 3. 添加所有记录的图片文件
+        for image in &images {
+            writer_image(&mut zip_writer, image, &sizes)?;
+        }
+        zip_writer.finish()?;
+        Ok(())
+    }
+}
+
+// 合并中的文档
+struct MergedDocument {
+    // 分隔方式
+    separator: MergeSeparator,
+    // 文档开头到<w:body>的内容
+    header: Vec<Event<'static>>,
+    // 合并后的正文内容
+    body: Writer<Cursor<Vec<u8>>>,
+    // 文档的页面设置
+    sect_pr: Vec<Event<'static>>,
+    // 已合并的份数
+    copies: usize,
+    // 下一个图片编号
+    next_drawing_id: u64,
+    // 下一个书签编号
+    next_bookmark_id: u64,
+}
+
+impl MergedDocument {
+    fn new(separator: MergeSeparator) -> Self {
+        MergedDocument {
+            separator,
+            header: Vec::new(),
+            body: Writer::new(Cursor::new(Vec::new())),
+            sect_pr: Vec::new(),
+            copies: 0,
+            next_drawing_id: 1,
+            next_bookmark_id: 0,
+        }
+    }
+
+    /// 追加一份渲染后的文档内容
+    /// @param contents 渲染后的document.xml
+    /// @param relation_map 需要替换的图片关系编号
+    fn append(
+        &mut self,
+        contents: &[u8],
+        relation_map: &HashMap<String, String>,
+    ) -> Result<(), DocxError> {
+        if self.copies > 0 {
+            self.write_separator()?;
+        }
+        let mut reader = quick_xml::Reader::from_reader(contents);
+        let mut buf = Vec::new();
+        let mut bookmark_map: HashMap<Vec<u8>, String> = HashMap::new();
+        let bookmark_names = self.bookmark_names(contents)?;
+        // 是否在域代码中
+        let mut instruction = false;
+        // 当前标签深度，正文子元素的深度为3
+        let mut depth = 0;
+        let mut in_body = false;
+        let mut sect_pr: Option<Vec<Event<'static>>> = None;
+        loop {
+            let event = reader.read_event_into(&mut buf)?;
+            match &event {
+                Event::Eof => break,
+                Event::Start(e) => {
+                    depth += 1;
+                    if !in_body {
+                        in_body = e.name().as_ref() == b"w:body";
+                        if self.copies == 0 {
+                            self.header.push(event.into_owned());
+                        }
+                    } else if depth == 3 && e.name().as_ref() == b"w:sectPr" {
+                        sect_pr = Some(vec![event.into_owned()]);
+                    } else if let Some(sect_pr) = sect_pr.as_mut() {
+                        sect_pr.push(event.into_owned());
+                    } else {
+                        instruction = e.name().as_ref() == b"w:instrText";
+                        let element =
+                            self.renumber(e, relation_map, &mut bookmark_map, &bookmark_names);
+                        self.body.write_event(Event::Start(element))?;
+                    }
+                }
+                Event::Empty(e) => {
+                    if !in_body {
+                        if self.copies == 0 {
+                            self.header.push(event.into_owned());
+                        }
+                    } else if depth == 2 && e.name().as_ref() == b"w:sectPr" {
+                        self.sect_pr = vec![event.into_owned()];
+                    } else if let Some(sect_pr) = sect_pr.as_mut() {
+                        sect_pr.push(event.into_owned());
+                    } else {
+                        let element =
+                            self.renumber(e, relation_map, &mut bookmark_map, &bookmark_names);
+                        self.body.write_event(Event::Empty(element))?;
+                    }
+                }
+                Event::End(e) => {
+                    if let Some(mut events) = sect_pr.take() {
+                        events.push(event.into_owned());
+                        if depth == 3 {
+                            self.sect_pr = events;
+                        } else {
+                            sect_pr = Some(events);
+                        }
+                    } else if in_body && e.name().as_ref() == b"w:body" {
+                        in_body = false;
+                    } else if in_body {
+                        instruction = false;
+                        self.body.write_event(event)?;
+                    }
+                    depth -= 1;
+                }
+                _ => {
+                    if !in_body {
+                        if self.copies == 0 && depth < 2 {
+                            self.header.push(event.into_owned());
+                        }
+                    } else if let Some(sect_pr) = sect_pr.as_mut() {
+                        sect_pr.push(event.into_owned());
+                    } else if let (true, Event::Text(text)) = (instruction, &event) {
+                        // 域代码中引用的书签名称随书签一起替换
+                        let text = text.unescape()?;
+                        let text =
+                            rename_field(&text, &bookmark_names).unwrap_or(text.into_owned());
+                        self.body.write_event(Event::Text(BytesText::new(&text)))?;
+                    } else {
+                        self.body.write_event(event)?;
+                    }
+                }
+            }
+            buf.clear();
+        }
+        self.copies += 1;
+        Ok(())
+    }
+
+    /// 写入每份内容之间的分隔段落
+    fn write_separator(&mut self) -> Result<(), DocxError> {
+        self.body
+            .write_event(Event::Start(BytesStart::new("w:p")))?;
+        match self.separator {
+            MergeSeparator::PageBreak => {
+                self.body
+                    .write_event(Event::Start(BytesStart::new("w:r")))?;
+                self.body.write_event(Event::Empty(
+                    BytesStart::new("w:br").with_attributes([("w:type", "page")]),
+                ))?;
+                self.body.write_event(Event::End(BytesEnd::new("w:r")))?;
+            }
+            MergeSeparator::SectionBreak => {
+                self.body
+                    .write_event(Event::Start(BytesStart::new("w:pPr")))?;
+                for event in &self.sect_pr {
+                    self.body.write_event(event.borrow())?;
+                }
+                self.body.write_event(Event::End(BytesEnd::new("w:pPr")))?;
+            }
+        }
+        self.body.write_event(Event::End(BytesEnd::new("w:p")))?;
+        Ok(())
+    }
+
+    /// 获取一份内容中需要重命名的书签名称
+    /// @param contents 渲染后的document.xml
+    /// @return 原书签名称到新名称的映射
+    fn bookmark_names(&self, contents: &[u8]) -> Result<HashMap<String, String>, DocxError> {
+        let mut names = HashMap::new();
+        // 第一份内容保留原书签名称
+        if self.copies == 0 {
+            return Ok(names);
+        }
+        let mut reader = quick_xml::Reader::from_reader(contents);
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Eof => break,
+                Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"w:bookmarkStart" => {
+                    let name = e
+                        .attributes()
+                        .flatten()
+                        .find(|attribute| attribute.key.as_ref() == b"w:name");
+                    if let Some(name) = name {
+                        let name = name.unescape_value()?.into_owned();
+                        if name != "_GoBack" {
+                            let renamed = format!("{}_{}", name, self.copies + 1);
+                            names.insert(name, renamed);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            buf.clear();
+        }
+        Ok(names)
+    }
+
+    /// 重新编号图片、书签，并替换冲突的关系编号和书签名称
+    fn renumber(
+        &mut self,
+        element: &BytesStart,
+        relation_map: &HashMap<String, String>,
+        bookmark_map: &mut HashMap<Vec<u8>, String>,
+        bookmark_names: &HashMap<String, String>,
+    ) -> BytesStart<'static> {
+        let name = element.name();
+        let is_drawing = name.as_ref() == b"wp:docPr";
+        let is_bookmark = matches!(name.as_ref(), b"w:bookmarkStart" | b"w:bookmarkEnd");
+        // 引用书签的超链接和域
+        let is_reference =
+            !bookmark_names.is_empty() && matches!(name.as_ref(), b"w:hyperlink" | b"w:fldSimple");
+        let has_relation = !relation_map.is_empty()
+            && element
+                .attributes()
+                .flatten()
+                .any(|a| a.key.prefix().is_some_and(|p| p.as_ref() == b"r"));
+        if !is_drawing && !is_bookmark && !is_reference && !has_relation {
+            return element.to_owned().into_owned();
+        }
+
+        let mut result = BytesStart::new(String::from_utf8_lossy(name.as_ref()).into_owned());
+        for attribute in element.attributes().flatten() {
+            let key = attribute.key.as_ref();
+            let value = attribute
+                .unescape_value()
+                .map(|v| v.into_owned())
+                .unwrap_or_default();
+            let value = match key {
+                b"id" if is_drawing => {
+                    self.next_drawing_id += 1;
+                    (self.next_drawing_id - 1).to_string()
+                }
+                b"w:id" if is_bookmark => bookmark_map
+                    .entry(value.into_bytes())
+                    .or_insert_with(|| {
+                        self.next_bookmark_id += 1;
+                        (self.next_bookmark_id - 1).to_string()
+                    })
+                    .clone(),
+                b"w:name" | b"w:anchor" if is_bookmark || is_reference => {
+                    bookmark_names.get(&value).cloned().unwrap_or(value)
+                }
+                b"w:instr" if is_reference => rename_field(&value, bookmark_names).unwrap_or(value),
+                _ if attribute.key.prefix().is_some_and(|p| p.as_ref() == b"r") => {
+                    relation_map.get(&value).cloned().unwrap_or(value)
+                }
+                _ => value,
+            };
+            let key = String::from_utf8_lossy(key);
+            result.push_attribute(Attribute::from((key.as_ref(), value.as_str())));
+        }
+        result
+    }
+
+    /// 生成合并后的document.xml
+    fn finish(&self) -> Result<Vec<u8>, DocxError> {
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        for event in &self.header {
+            writer.write_event(event.borrow())?;
+        }
+        writer.get_mut().write_all(self.body.get_ref().get_ref())?;
+        for event in &self.sect_pr {
+            writer.write_event(event.borrow())?;
+        }
+        writer.write_event(Event::End(BytesEnd::new("w:body")))?;
+        writer.write_event(Event::End(BytesEnd::new("w:document")))?;
+        Ok(writer.into_inner().into_inner())
+    }
+}
+
+/// 替换域代码中引用的书签名称
+/// @param instruction 域代码
+/// @param bookmark_names 原书签名称到新名称的映射
+/// @return 替换后的域代码，不需要替换时返回None
+fn rename_field(instruction: &str, bookmark_names: &HashMap<String, String>) -> Option<String> {
+    let mut words = instruction.split_whitespace();
+    let field = words.next()?;
+    if !["REF", "PAGEREF", "NOTEREF"]
+        .iter()
+        .any(|name| field.eq_ignore_ascii_case(name))
+    {
+        return None;
+    }
+    let bookmark = words.next()?;
+    let renamed = bookmark_names.get(bookmark)?;
+    // 保留域代码原有的空白和开关
+    let start = instruction.find(field)? + field.len();
+    let offset = start + instruction[start..].find(bookmark)?;
+    Some(format!(
+        "{}{}{}",
+        &instruction[..offset],
+        renamed,
+        &instruction[offset + bookmark.len()..]
+    ))
+}
@@ -2,27 +2,44 @@ use std::io::Write;
 use quick_xml::events::Event;
 use quick_xml::Writer;
 use crate::error::DocxError;
+use crate::style::ImageStyle;
 
 
 /// 创建图片标签  
 /// @param writer 写入对象  
 /// @param relation_id 关联图片编号  
+/// @param svg_relation_id SVG图片关联编号，relation_id为PNG备用图片
 /// @param width 图片宽度  
 /// @param height 图片高度  
+/// @param crop 裁剪比例（1/100000），左、上、右、下
+/// @param style 图片样式：旋转、翻转、边框和阴影
 pub fn create_drawing_element<T>(
     writer: &mut Writer<T>,
     relation_id: &str,
+    svg_relation_id: Option<&str>,
     width: u64,
     height: u64,
+    crop: Option<[u32; 4]>,
+    style: &ImageStyle,
 ) -> Result<(), DocxError>
 where
     T: Write,
 {
+    let src_rect = match crop {
+        Some([l, t, r, b]) => format!(r#"<a:srcRect l="{}" t="{}" r="{}" b="{}"/>"#, l, t, r, b),
+        None => String::new(),
+    };
+    // 旋转和边框超出图片大小的部分
+    let effect_extent = match style.effect_extent(width, height) {
+        (0, 0) => String::new(),
+        (x, y) => format!(r#"<wp:effectExtent l="{}" t="{}" r="{}" b="{}"/>"#, x, y, x, y),
+    };
     let drawing = format!(
         r#"
         <w:drawing>
             <wp:inline distT="0" distB="0" distL="0" distR="0">
                 <wp:extent cx="{}" cy="{}"/>
+                {}
                 <wp:docPr id="1" name="Picture 1" descr="Generated image"/>
                 <wp:cNvGraphicFramePr>
                     <a:graphicFrameLocks xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" noChangeAspect="1"/>
@@ -35,19 +52,21 @@ where
                                 <pic:cNvPicPr><a:picLocks noChangeAspect="1"/></pic:cNvPicPr>
                             </pic:nvPicPr>
                             <pic:blipFill>
-                                <a:blip r:embed="{}"/>
+                                <a:blip r:embed="{}">{}</a:blip>
+                                {}
                                 <a:stretch>
                                     <a:fillRect/>
                                 </a:stretch>
                             </pic:blipFill>
                             <pic:spPr>
-                                <a:xfrm>
+                                <a:xfrm{}>
                                     <a:off x="0" y="0"/>
                                     <a:ext cx="{}" cy="{}"/>
                                 </a:xfrm>
                                 <a:prstGeom prst="rect">
                                     <a:avLst/>
                                 </a:prstGeom>
+                                {}
                             </pic:spPr>
                         </pic:pic>
                     </a:graphicData>
@@ -55,10 +74,36 @@ where
             </wp:inline>
         </w:drawing>
     "#,
-        width, height, relation_id, width, height,
+        width, height, effect_extent, relation_id,
+        svg_relation_id.map(svg_extension).unwrap_or_default(), src_rect,
+        style.xfrm_attributes(), width, height, style.effect_elements(),
     );
+    write_xml(writer, &drawing)
+}
 
-    let mut reader = quick_xml::Reader::from_str(&drawing);
+/// 创建a:blip中引用SVG图片的扩展标签
+/// @param svg_relation_id SVG图片关联编号
+pub fn svg_extension(svg_relation_id: &str) -> String {
+    format!("<a:extLst>{}</a:extLst>", svg_ext(svg_relation_id))
+}
+
+/// 创建引用SVG图片的扩展项，用于加入a:blip中已有的扩展列表
+/// @param svg_relation_id SVG图片关联编号
+pub fn svg_ext(svg_relation_id: &str) -> String {
+    format!(
+        r#"<a:ext uri="{{96DAC541-7B7A-43D3-8B79-37D633B846F1}}"><asvg:svgBlip xmlns:asvg="http://schemas.microsoft.com/office/drawing/2016/SVG/main" r:embed="{}"/></a:ext>"#,
+        svg_relation_id
+    )
+}
+
+/// 将xml字符串写入文档
+/// @param writer 写入对象
+/// @param xml xml字符串
+pub fn write_xml<T>(writer: &mut Writer<T>, xml: &str) -> Result<(), DocxError>
+where
+    T: Write,
+{
+    let mut reader = quick_xml::Reader::from_str(xml);
     reader.config_mut().trim_text(true);
     let mut buf = Vec::new();
 
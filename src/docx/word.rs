@@ -1,6 +1,10 @@
 
 /// word文件中内容xml文件
 pub static WORD_DOCUMENT:&str = "word/document.xml";
+/// word文件中内容类型xml文件
+pub static CONTENT_TYPES:&str = "[Content_Types].xml";
+/// word文件中样式xml文件
+pub static WORD_STYLES:&str = "word/styles.xml";
 /// word文件中关联图片关系xml
 pub static WORD_RELS_DOCUMENT:&str = "word/_rels/document.xml.rels";
 /// word文件中图片名称
@@ -12,10 +12,33 @@ pub enum DocxError {
     Utf8(#[from] std::string::FromUtf8Error),
     #[error("Image not found: {0}")]
     ImageNotFound(String),
+    #[cfg(feature = "http")]
     #[error("Image url not found: {0}")]
     InvalidImageUrl(#[from] reqwest::Error),
     #[error("read image size error: {0}")]
     ReadImageSize(#[from] image::ImageError),
     #[error("Not image content type error: {0}")]
     NotImage(String),
+    #[error("Format value error: {0}")]
+    Format(String),
+    #[error("Expression error: {0}")]
+    Expression(String),
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+    #[error("Image fetch failed: {0}")]
+    FetchFailed(String),
+    #[error("Image fetcher config error: {0}")]
+    FetcherConfig(String),
+    #[error("Image too large: {0}")]
+    ImageTooLarge(String),
+    #[error("Image url not allowed: {0}")]
+    UrlNotAllowed(String),
+    #[error("Invalid base64 image: {0}")]
+    InvalidBase64(String),
+    #[cfg(feature = "barcode")]
+    #[error("Barcode error: {0}")]
+    Barcode(String),
+    #[cfg(feature = "svg")]
+    #[error("SVG error: {0}")]
+    Svg(#[from] resvg::usvg::Error),
 }
@@ -0,0 +1,343 @@
+use crate::error::DocxError;
+use std::fmt::{Display, Formatter};
+
+/// 表达式计算结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    // 数字
+    Number(f64),
+    // 字符串
+    Text(String),
+    // 布尔值
+    Bool(bool),
+    // 数据模型中的值，保留原始字符串，参与算术运算和比较时才转换为数字
+    Data(String),
+}
+
+impl Value {
+    /// 将数据模型中的字符串转换为值，原样保留前导0、长数字编号和千分位
+    /// @param value 字符串
+    pub fn from_data(value: &str) -> Self {
+        Value::Data(value.to_string())
+    }
+
+    /// 判断值是否为真，空字符串、0、false 为假
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(number) => *number != 0.0,
+            Value::Text(text) => !text.is_empty() && text != "false",
+            Value::Bool(value) => *value,
+            Value::Data(text) => match parse_number(text) {
+                Some(number) => number != 0.0,
+                None => !text.is_empty() && text != "false",
+            },
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(text) => parse_number(text),
+            Value::Bool(_) => None,
+            Value::Data(text) => parse_number(text),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(number) => {
+                // 保留10位小数并去掉末尾的0，避免浮点误差
+                let text = format!("{:.10}", number);
+                let text = text.trim_end_matches('0').trim_end_matches('.');
+                write!(f, "{}", if text == "-0" { "0" } else { text })
+            }
+            Value::Text(text) | Value::Data(text) => write!(f, "{}", text),
+            Value::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// 计算表达式，例如 qty * price、a == "yes"、ok ? "是" : "否"
+/// @param expression 表达式
+/// @param lookup 变量查询函数，返回数据模型中的值
+/// @return 计算结果
+pub fn evaluate<F>(expression: &str, lookup: F) -> Result<Value, DocxError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        lookup: &lookup,
+    };
+    let value = parser.parse_ternary()?;
+    if parser.position < parser.tokens.len() {
+        return Err(DocxError::Expression(format!(
+            "unexpected token in: {}",
+            expression
+        )));
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+// 运算符，长的在前以便优先匹配
+static OPERATORS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "(", ")", "?", ":",
+];
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, DocxError> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c == '"' || c == '\'' || c == '“' {
+            // 字符串常量
+            let close = if c == '“' { '”' } else { c };
+            let body = &rest[c.len_utf8()..];
+            let end = body.find(close).ok_or_else(|| {
+                DocxError::Expression(format!("unterminated string in: {}", expression))
+            })?;
+            tokens.push(Token::Text(body[..end].to_string()));
+            rest = &body[end + close.len_utf8()..];
+        } else if c.is_ascii_digit() {
+            // 数字常量
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..end]
+                .parse::<f64>()
+                .map_err(|_| DocxError::Expression(format!("invalid number: {}", &rest[..end])))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            // 变量名，支持中文以及 a.b 形式
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let operator = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| {
+                    DocxError::Expression(format!(
+                        "unexpected character '{}' in: {}",
+                        c, expression
+                    ))
+                })?;
+            tokens.push(Token::Op(operator));
+            rest = &rest[operator.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a, F> {
+    tokens: Vec<Token>,
+    position: usize,
+    lookup: &'a F,
+}
+
+impl<F> Parser<'_, F>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if self.peek_op() == Some(op) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), DocxError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(DocxError::Expression(format!("expected '{}'", op)))
+        }
+    }
+
+    fn parse_ternary(&mut self) -> Result<Value, DocxError> {
+        let condition = self.parse_or()?;
+        if self.eat("?") {
+            let then = self.parse_ternary()?;
+            self.expect(":")?;
+            let otherwise = self.parse_ternary()?;
+            Ok(if condition.is_truthy() {
+                then
+            } else {
+                otherwise
+            })
+        } else {
+            Ok(condition)
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Value, DocxError> {
+        let mut left = self.parse_and()?;
+        while self.eat("||") {
+            let right = self.parse_and()?;
+            left = Value::Bool(left.is_truthy() || right.is_truthy());
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Value, DocxError> {
+        let mut left = self.parse_comparison()?;
+        while self.eat("&&") {
+            let right = self.parse_comparison()?;
+            left = Value::Bool(left.is_truthy() && right.is_truthy());
+        }
+        Ok(left)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Value, DocxError> {
+        let left = self.parse_additive()?;
+        let Some(op) = self
+            .peek_op()
+            .filter(|op| matches!(*op, "==" | "!=" | "<" | "<=" | ">" | ">="))
+        else {
+            return Ok(left);
+        };
+        self.position += 1;
+        let right = self.parse_additive()?;
+        let ordering = match (left.as_number(), right.as_number()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => Some(left.to_string().cmp(&right.to_string())),
+        };
+        let result = match op {
+            "==" => ordering == Some(std::cmp::Ordering::Equal),
+            "!=" => ordering != Some(std::cmp::Ordering::Equal),
+            "<" => ordering == Some(std::cmp::Ordering::Less),
+            "<=" => matches!(
+                ordering,
+                Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal)
+            ),
+            ">" => ordering == Some(std::cmp::Ordering::Greater),
+            _ => matches!(
+                ordering,
+                Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal)
+            ),
+        };
+        Ok(Value::Bool(result))
+    }
+
+    fn parse_additive(&mut self) -> Result<Value, DocxError> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            if self.eat("+") {
+                let right = self.parse_multiplicative()?;
+                // 两边都是数字时相加，否则按原始字符串拼接
+                left = match (numeric(&left), numeric(&right)) {
+                    (Some(a), Some(b)) => Value::Number(a + b),
+                    _ => Value::Text(format!("{}{}", left, right)),
+                };
+            } else if self.eat("-") {
+                let right = self.parse_multiplicative()?;
+                left = Value::Number(number(&left)? - number(&right)?);
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Value, DocxError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek_op() {
+                Some(op @ ("*" | "/" | "%")) => op,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.parse_unary()?;
+            let (a, b) = (number(&left)?, number(&right)?);
+            if op != "*" && b == 0.0 {
+                return Err(DocxError::Expression("division by zero".to_string()));
+            }
+            left = Value::Number(match op {
+                "*" => a * b,
+                "/" => a / b,
+                _ => a % b,
+            });
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Value, DocxError> {
+        if self.eat("!") {
+            Ok(Value::Bool(!self.parse_unary()?.is_truthy()))
+        } else if self.eat("-") {
+            Ok(Value::Number(-number(&self.parse_unary()?)?))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Value, DocxError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| DocxError::Expression("unexpected end of expression".to_string()))?;
+        self.position += 1;
+        match token {
+            Token::Number(number) => Ok(Value::Number(number)),
+            Token::Text(text) => Ok(Value::Text(text)),
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => (self.lookup)(&name)
+                    .map(|value| Value::from_data(&value))
+                    .ok_or_else(|| DocxError::Expression(format!("unknown variable: {}", name))),
+            },
+            Token::Op("(") => {
+                let value = self.parse_ternary()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Token::Op(op) => Err(DocxError::Expression(format!("unexpected '{}'", op))),
+        }
+    }
+}
+
+fn number(value: &Value) -> Result<f64, DocxError> {
+    value
+        .as_number()
+        .ok_or_else(|| DocxError::Expression(format!("not a number: {}", value)))
+}
+
+/// 数字或可解析为数字的数据模型值，字符串常量不参与相加
+fn numeric(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(_) | Value::Data(_) => value.as_number(),
+        _ => None,
+    }
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    let text: String = value.trim().chars().filter(|c| *c != ',').collect();
+    if text.is_empty() || !text.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    text.parse::<f64>().ok().filter(|n| n.is_finite())
+}
@@ -0,0 +1,39 @@
+use crate::error::DocxError;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// 图片获取结果：(data, ext) 图片数据和图片扩展名
+pub type FetchResult = Result<(Vec<u8>, String), DocxError>;
+
+/// 异步获取图片的返回值
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = FetchResult> + Send + 'a>>;
+
+/// 图片获取接口，用于根据图片引用（url、对象存储路径等）获取图片数据
+pub trait ImageFetcher: Send + Sync {
+    /// 获取图片数据
+    /// @param url 图片引用
+    /// @return (data, ext) 返回 Vec<u8>和图片扩展名
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a>;
+}
+
+/// 同步图片获取接口，用于非异步程序
+pub trait BlockingImageFetcher: Send + Sync {
+    /// 获取图片数据
+    /// @param url 图片引用
+    /// @return (data, ext) 返回 Vec<u8>和图片扩展名
+    fn fetch(&self, url: &str) -> FetchResult;
+}
+
+// 共享的图片获取对象，例如多个模板共用一个带缓存的获取对象
+impl<T: ImageFetcher + ?Sized> ImageFetcher for Arc<T> {
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+        self.as_ref().fetch(url)
+    }
+}
+
+impl<T: BlockingImageFetcher + ?Sized> BlockingImageFetcher for Arc<T> {
+    fn fetch(&self, url: &str) -> FetchResult {
+        self.as_ref().fetch(url)
+    }
+}
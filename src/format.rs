@@ -0,0 +1,254 @@
+use crate::error::DocxError;
+
+// 大写数字
+static UPPER_DIGITS: [char; 10] = ['零', '壹', '贰', '叁', '肆', '伍', '陆', '柒', '捌', '玖'];
+// 大写数字组内单位
+static UPPER_UNITS: [&str; 4] = ["", "拾", "佰", "仟"];
+// 小写数字
+static LOWER_DIGITS: [char; 10] = ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+// 数字组单位（每四位一组）
+static GROUP_UNITS: [&str; 4] = ["", "万", "亿", "万"];
+
+/// 将金额转换为人民币大写，例如 10000 -> 壹万元整
+/// @param value 金额字符串，支持 ¥、逗号分隔以及两位小数
+/// @return 大写金额
+pub fn to_rmb_upper(value: &str) -> Result<String, DocxError> {
+    let (negative, cents) = parse_cents(value)?;
+    let yuan = cents / 100;
+    let jiao = (cents / 10 % 10) as usize;
+    let fen = (cents % 10) as usize;
+
+    let mut result = String::new();
+    if negative {
+        result.push('负');
+    }
+    if yuan > 0 {
+        result.push_str(&integer_upper(yuan));
+        result.push('元');
+    }
+    match (jiao, fen) {
+        (0, 0) => {
+            if yuan == 0 {
+                result.push_str("零元");
+            }
+            result.push('整');
+        }
+        _ => {
+            if jiao > 0 {
+                result.push(UPPER_DIGITS[jiao]);
+                result.push('角');
+            } else if yuan > 0 {
+                result.push('零');
+            }
+            if fen > 0 {
+                result.push(UPPER_DIGITS[fen]);
+                result.push('分');
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// 将整数转换为中文大写数字，例如 12000 -> 壹万贰仟
+/// @param value 整数字符串，支持逗号分隔
+/// @return 大写数字
+pub fn to_chinese_upper(value: &str) -> Result<String, DocxError> {
+    let text: String = value.trim().chars().filter(|c| *c != ',').collect();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.as_str()),
+    };
+    let number = digits
+        .parse::<u64>()
+        .map_err(|_| DocxError::Format(format!("not an integer: {}", value)))?;
+    if number >= 10u64.pow(16) {
+        return Err(DocxError::Format(format!("number too large: {}", value)));
+    }
+    let upper = if number == 0 {
+        UPPER_DIGITS[0].to_string()
+    } else {
+        integer_upper(number)
+    };
+    Ok(if negative {
+        format!("负{}", upper)
+    } else {
+        upper
+    })
+}
+
+/// 将日期转换为中文格式，例如 2023-11-25 -> 2023年11月25日
+/// @param value 日期字符串，支持 -、/、. 分隔
+/// @return 中文日期
+pub fn to_chinese_date(value: &str) -> Result<String, DocxError> {
+    let (year, month, day) = parse_date(value)?;
+    Ok(format!("{}年{}月{}日", year, month, day))
+}
+
+/// 将日期转换为中文小写数字格式，例如 2023-11-25 -> 二〇二三年十一月二十五日
+/// @param value 日期字符串，支持 -、/、. 分隔
+/// @return 中文日期
+pub fn to_chinese_date_upper(value: &str) -> Result<String, DocxError> {
+    let (year, month, day) = parse_date(value)?;
+    let year: String = year
+        .to_string()
+        .chars()
+        .map(|c| LOWER_DIGITS[c.to_digit(10).unwrap_or(0) as usize])
+        .collect();
+    Ok(format!(
+        "{}年{}月{}日",
+        year,
+        small_number_lower(month),
+        small_number_lower(day)
+    ))
+}
+
+/// 将半角字符转换为全角字符，例如 A1 -> Ａ１
+/// @param value 字符串
+/// @return 全角字符串
+pub fn to_full_width(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ' ' => '\u{3000}',
+            '!'..='~' => char::from_u32(c as u32 + 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// 将字符串中的半角数字转换为全角数字，例如 2023 -> ２０２３
+/// @param value 字符串
+/// @return 转换后的字符串
+pub fn to_full_width_digits(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '0'..='9' => char::from_u32(c as u32 + 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// 按名称调用格式化过滤器，供占位符过滤语法 {{name|filter}} 使用
+/// @param name 过滤器名称
+/// @param value 待格式化的值
+/// @return 格式化后的值
+pub fn apply_filter(name: &str, value: &str) -> Result<String, DocxError> {
+    match name.trim() {
+        "rmb_upper" => to_rmb_upper(value),
+        "cn_upper" => to_chinese_upper(value),
+        "cn_date" => to_chinese_date(value),
+        "cn_date_upper" => to_chinese_date_upper(value),
+        "fullwidth" => Ok(to_full_width(value)),
+        "fullwidth_digits" => Ok(to_full_width_digits(value)),
+        other => Err(DocxError::Format(format!("unknown filter: {}", other))),
+    }
+}
+
+/// 解析金额为分，四舍五入到分
+fn parse_cents(value: &str) -> Result<(bool, u64), DocxError> {
+    let text: String = value
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ',' | '，' | '¥' | '￥' | ' '))
+        .collect();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text.as_str()),
+    };
+    let invalid = || DocxError::Format(format!("not an amount: {}", value));
+    let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if (integer.is_empty() && fraction.is_empty())
+        || !integer.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    let yuan = if integer.is_empty() {
+        0
+    } else {
+        integer.parse::<u64>().map_err(|_| invalid())?
+    };
+    let mut fraction_digits = fraction.bytes().map(|b| (b - b'0') as u64);
+    let jiao = fraction_digits.next().unwrap_or(0);
+    let fen = fraction_digits.next().unwrap_or(0);
+    let round = u64::from(fraction_digits.next().unwrap_or(0) >= 5);
+    let cents = yuan
+        .checked_mul(100)
+        .and_then(|c| c.checked_add(jiao * 10 + fen + round))
+        .ok_or_else(invalid)?;
+    if cents / 100 >= 10u64.pow(16) {
+        return Err(DocxError::Format(format!("amount too large: {}", value)));
+    }
+    Ok((negative && cents > 0, cents))
+}
+
+/// 解析日期字符串为年月日
+fn parse_date(value: &str) -> Result<(u32, u32, u32), DocxError> {
+    let invalid = || DocxError::Format(format!("not a date: {}", value));
+    let mut parts = value
+        .trim()
+        .split(['-', '/', '.'])
+        .map(|part| part.parse::<u32>().map_err(|_| invalid()));
+    let year = parts.next().ok_or_else(invalid)??;
+    let month = parts.next().ok_or_else(invalid)??;
+    let day = parts.next().ok_or_else(invalid)??;
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    Ok((year, month, day))
+}
+
+/// 将正整数转换为大写数字（不含“元”）
+fn integer_upper(number: u64) -> String {
+    let digits: Vec<usize> = number
+        .to_string()
+        .bytes()
+        .map(|b| (b - b'0') as usize)
+        .collect();
+    let len = digits.len();
+    let mut result = String::new();
+    let mut zero_pending = false;
+    for (i, digit) in digits.iter().enumerate() {
+        let position = len - 1 - i;
+        if *digit == 0 {
+            zero_pending = true;
+        } else {
+            if zero_pending {
+                result.push(UPPER_DIGITS[0]);
+                zero_pending = false;
+            }
+            result.push(UPPER_DIGITS[*digit]);
+            result.push_str(UPPER_UNITS[position % 4]);
+        }
+        // 每组结束时添加组单位（万、亿），整组为零时跳过，亿位之前的万亿组也需要补“亿”
+        if position % 4 == 0 && position > 0 {
+            let group = if position == 8 {
+                0
+            } else {
+                i.saturating_sub(3)
+            };
+            if digits[group..=i].iter().any(|d| *d != 0) {
+                result.push_str(GROUP_UNITS[position / 4]);
+            }
+        }
+    }
+    result
+}
+
+/// 将 1-99 的整数转换为中文小写，例如 25 -> 二十五
+fn small_number_lower(number: u32) -> String {
+    let tens = (number / 10) as usize;
+    let ones = (number % 10) as usize;
+    let mut result = String::new();
+    if tens > 1 {
+        result.push(LOWER_DIGITS[tens]);
+    }
+    if tens > 0 {
+        result.push('十');
+    }
+    if ones > 0 || tens == 0 {
+        result.push(LOWER_DIGITS[ones]);
+    }
+    result
+}
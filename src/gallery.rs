@@ -0,0 +1,70 @@
+use crate::image::DocxImage;
+use crate::size::Length;
+
+/// 多张图片的排列方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GalleryLayout {
+    // 图片在同一段落中依次排列，参数为图片之间的间距
+    Inline(Length),
+    // 生成无边框表格，参数为每行的列数
+    Grid(usize),
+}
+
+/// 图片组中的图片
+#[derive(Debug, Clone)]
+pub struct GalleryImage {
+    // 图片
+    pub image: DocxImage,
+    // 图片说明，表格排列时显示在图片下方
+    pub caption: Option<String>,
+}
+
+/// 同一占位符替换的多张图片，例如数量不固定的现场照片
+#[derive(Debug, Clone)]
+pub struct ImageGallery {
+    // 排列方式
+    pub layout: GalleryLayout,
+    // 图片列表
+    pub images: Vec<GalleryImage>,
+}
+
+impl ImageGallery {
+    /// 创建在同一段落中依次排列的图片组
+    /// @param spacing 图片之间的间距
+    pub fn inline(spacing: Length) -> Self {
+        ImageGallery {
+            layout: GalleryLayout::Inline(spacing),
+            images: Vec::new(),
+        }
+    }
+
+    /// 创建表格排列的图片组，图片按单元格宽度缩小
+    /// @param columns 每行的列数，最少为1
+    pub fn grid(columns: usize) -> Self {
+        ImageGallery {
+            layout: GalleryLayout::Grid(columns.max(1)),
+            images: Vec::new(),
+        }
+    }
+
+    /// 添加图片
+    /// @param image 图片
+    pub fn add_image(mut self, image: DocxImage) -> Self {
+        self.images.push(GalleryImage {
+            image,
+            caption: None,
+        });
+        self
+    }
+
+    /// 添加带说明的图片
+    /// @param image 图片
+    /// @param caption 图片说明
+    pub fn add_image_caption(mut self, image: DocxImage, caption: &str) -> Self {
+        self.images.push(GalleryImage {
+            image,
+            caption: Some(caption.to_string()),
+        });
+        self
+    }
+}
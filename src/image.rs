@@ -1,7 +1,15 @@
+use crate::caption::ImageCaption;
 use crate::error::DocxError;
-use image::{GenericImageView, load_from_memory};
+use crate::style::ImageStyle;
+use crate::transform::ImageOptions;
+use base64::Engine;
+use base64::alphabet::STANDARD;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use image::metadata::Orientation;
+use image::{GenericImageView, ImageDecoder, ImageReader, load_from_memory};
+use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Cursor, Read};
 use std::path::Path;
 use uuid::Uuid;
 
@@ -11,7 +19,9 @@ pub static DOCX_MAX_EMU: u64 = (21.0 * 360000.0 / 2.0) as u64;
 // 1英寸=96像素
 static DPI: f64 = 96f64;
 // 1英寸=914400 EMU
-static EMU: f64 = 914400f64;
+pub(crate) static EMU: f64 = 914400f64;
+// 裁剪比例的单位，100000表示100%
+pub(crate) static CROP_UNIT: f64 = 100000.0;
 
 // 添加的图标对象
 #[derive(Debug, Clone)]
@@ -20,6 +30,8 @@ pub struct DocxImage {
     pub image_path: String,
     // 图片扩展名
     pub image_ext: String,
+    // 图片内容类型，例如image/png
+    pub content_type: String,
     // 图片数据
     pub image_data: Vec<u8>,
     // 关联id
@@ -28,6 +40,30 @@ pub struct DocxImage {
     pub width: u64,
     // 图片高度
     pub height: u64,
+    // 是否为默认大小，写入文档时按页面或单元格宽度缩小
+    pub auto_size: bool,
+    // 裁剪比例（1/100000），左、上、右、下
+    pub crop: Option<[u32; 4]>,
+    // 图片样式
+    pub style: ImageStyle,
+    // SVG图片数据，此时image_data为转换后的PNG备用图片
+    pub svg_data: Option<Vec<u8>>,
+    // 图片题注
+    pub caption: Option<ImageCaption>,
+    // 添加图片时的处理选项，写入文档时按实际显示大小和目标DPI缩小图片
+    pub image_options: ImageOptions,
+}
+
+/// 在线图片获取失败时的处理方式
+#[derive(Debug, Clone, Default)]
+pub enum ImageFallback {
+    // 返回错误
+    #[default]
+    Fail,
+    // 不显示图片，清除占位符
+    Blank,
+    // 使用占位图片代替
+    Placeholder(Box<DocxImage>),
 }
 
 impl DocxImage {
@@ -38,10 +74,9 @@ impl DocxImage {
         let mut file = File::open(image_path)?;
         let mut image_data = Vec::new();
         file.read_to_end(&mut image_data)?;
-        // 获取扩展名
-        let ext = get_extension(image_path)?;
-        let (width_emu, height_emu) = get_image_size(&image_data)?;
-        Self::new_image_data_size(image_path, image_data, ext, width_emu, height_emu)
+        // 获取扩展名，图片格式优先根据内容判断
+        let ext = get_extension(image_path).unwrap_or_default();
+        Self::new_image_data(image_path, image_data, ext)
     }
 
     /// 设置图片大小  
@@ -53,8 +88,8 @@ impl DocxImage {
         let mut file = File::open(image_path)?;
         let mut image_data = Vec::new();
         file.read_to_end(&mut image_data)?;
-        // 获取扩展名
-        let ext = get_extension(image_path)?;
+        // 获取扩展名，图片格式优先根据内容判断
+        let ext = get_extension(image_path).unwrap_or_default();
         DocxImage::new_image_data_size(image_path, image_data, ext, width, height)
     }
 
@@ -66,8 +101,12 @@ impl DocxImage {
         image_data: Vec<u8>,
         image_ext: &str,
     ) -> Result<Self, DocxError> {
-        let (width_emu, height_emu) = get_image_size(&image_data)?;
-        DocxImage::new_image_data_size(image_url, image_data, image_ext, width_emu, height_emu)
+        let (width_emu, height_emu) = get_image_natural_size(&image_data)?;
+        let mut docx_image = DocxImage::new_image_data_size(
+            image_url, image_data, image_ext, width_emu, height_emu,
+        )?;
+        docx_image.auto_size = true;
+        Ok(docx_image)
     }
 
     /// 设置图片大小  
@@ -83,13 +122,25 @@ impl DocxImage {
         width: u64,
         height: u64,
     ) -> Result<Self, DocxError> {
+        let (image_data, svg_data) = split_svg(image_url, image_data)?;
+        let (image_ext, content_type) = resolve_format(&image_data, image_ext);
+        if image_ext.is_empty() {
+            return Err(DocxError::NotImage(image_url.to_string()));
+        }
         Ok(DocxImage {
             image_path: image_url.to_string(),
-            image_ext: image_ext.to_string(),
+            image_ext,
+            content_type,
             relation_id: format!("rId{}", Uuid::new_v4().simple()),
             width,
             height,
             image_data,
+            auto_size: false,
+            crop: None,
+            style: ImageStyle::default(),
+            svg_data,
+            caption: None,
+            image_options: ImageOptions::default(),
         })
     }
 
@@ -108,13 +159,21 @@ impl DocxImage {
         width: u64,
         height: u64,
     ) -> Self {
+        let (image_ext, content_type) = resolve_format(&image_data, image_ext);
         DocxImage {
             image_path: image_url.to_string(),
-            image_ext: image_ext.to_string(),
+            image_ext,
+            content_type,
             relation_id: relation_id.to_string(),
             width,
             height,
             image_data,
+            auto_size: false,
+            crop: None,
+            style: ImageStyle::default(),
+            svg_data: None,
+            caption: None,
+            image_options: ImageOptions::default(),
         }
     }
 
@@ -126,19 +185,110 @@ impl DocxImage {
         DocxImage {
             image_path: docx_image.image_path.clone(),
             image_ext: docx_image.image_ext.clone(),
+            content_type: docx_image.content_type.clone(),
             relation_id: docx_image.relation_id.clone(),
             width,
             height,
             image_data: docx_image.image_data.clone(),
+            auto_size: false,
+            crop: None,
+            style: ImageStyle::default(),
+            svg_data: docx_image.svg_data.clone(),
+            caption: None,
+            image_options: docx_image.image_options.clone(),
         }
     }
+
+    /// 图片内容的哈希值，内容相同的图片在文档中只保存一份
+    pub fn content_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update((self.image_data.len() as u64).to_le_bytes());
+        hasher.update(&self.image_data);
+        if let Some(svg_data) = &self.svg_data {
+            hasher.update(svg_data);
+        }
+        hasher.finalize().into()
+    }
+
+    /// SVG图片的关联编号，PNG备用图片使用relation_id
+    pub fn svg_relation_id(&self) -> String {
+        format!("{}svg", self.relation_id)
+    }
+
+    /// 裁剪后显示部分占原图片的比例（宽、高），样式中的裁剪优先
+    pub(crate) fn visible_fraction(&self) -> (f64, f64) {
+        crop_fraction(self.style.crop_rect().or(self.crop))
+    }
+
+    /// 裁剪后的显示大小（emu）
+    /// 图片大小按Cover裁剪后的大小设置，样式中的裁剪替换Cover裁剪时按整张图片的大小重新计算
+    pub(crate) fn cropped_size(&self) -> (u64, u64) {
+        let (visible_width, visible_height) = self.visible_fraction();
+        let (size_width, size_height) = crop_fraction(self.crop);
+        (
+            (self.width as f64 * visible_width / size_width) as u64,
+            (self.height as f64 * visible_height / size_height) as u64,
+        )
+    }
 }
 
+/// 裁剪后显示部分的比例（宽、高）
+/// @param crop 裁剪比例（1/100000），左、上、右、下
+fn crop_fraction(crop: Option<[u32; 4]>) -> (f64, f64) {
+    match crop {
+        Some([left, top, right, bottom]) => (
+            (1.0 - (left + right) as f64 / CROP_UNIT).max(f64::EPSILON),
+            (1.0 - (top + bottom) as f64 / CROP_UNIT).max(f64::EPSILON),
+        ),
+        None => (1.0, 1.0),
+    }
+}
+
+/// 判断图片数据是否为SVG
+/// @param image_data 图片数据
+pub fn is_svg(image_data: &[u8]) -> bool {
+    let head = &image_data[..image_data.len().min(4096)];
+    let head = String::from_utf8_lossy(head);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    head.starts_with('<') && head.contains("<svg")
+}
+
+/// SVG图片转换为PNG备用图片，其他图片原样返回
+/// @param image_url 图片路径
+/// @param image_data 图片数据
+/// @return (image_data, svg_data) 写入文档的图片数据和SVG图片数据
+#[cfg(feature = "svg")]
+fn split_svg(
+    _image_url: &str,
+    image_data: Vec<u8>,
+) -> Result<(Vec<u8>, Option<Vec<u8>>), DocxError> {
+    if !is_svg(&image_data) {
+        return Ok((image_data, None));
+    }
+    let png_data = crate::svg::rasterize_svg(&image_data)?;
+    Ok((png_data, Some(image_data)))
+}
+
+/// 没有启用svg特性时不支持SVG图片
+#[cfg(not(feature = "svg"))]
+fn split_svg(
+    image_url: &str,
+    image_data: Vec<u8>,
+) -> Result<(Vec<u8>, Option<Vec<u8>>), DocxError> {
+    if is_svg(&image_data) {
+        return Err(DocxError::NotImage(format!(
+            "{} is svg image, enable the svg feature",
+            image_url
+        )));
+    }
+    Ok((image_data, None))
+}
+
+/// 获取图片大小，宽度最大为半个A4纸宽度
+/// @param image_data 图片数据
+/// @return (width, height) 图片大小（emu）
 pub fn get_image_size(image_data: &[u8]) -> Result<(u64, u64), DocxError> {
-    let img = load_from_memory(image_data)?;
-    let (width_px, height_px) = img.dimensions();
-    let mut width_emu = (width_px as f64 * EMU / DPI) as u64;
-    let mut height_emu = (height_px as f64 * EMU / DPI) as u64;
+    let (mut width_emu, mut height_emu) = get_image_natural_size(image_data)?;
     // 判断图片是否大于文档宽度
     if width_emu > DOCX_MAX_EMU {
         height_emu = DOCX_MAX_EMU * height_emu / width_emu;
@@ -149,6 +299,177 @@ pub fn get_image_size(image_data: &[u8]) -> Result<(u64, u64), DocxError> {
     }
 }
 
+/// 按图片记录的DPI获取图片原始大小，没有DPI信息时按96DPI计算
+/// @param image_data 图片数据
+/// @return (width, height) 图片大小（emu）
+pub fn get_image_natural_size(image_data: &[u8]) -> Result<(u64, u64), DocxError> {
+    // SVG图片的大小单位为像素
+    #[cfg(feature = "svg")]
+    if is_svg(image_data) {
+        let (width_px, height_px) = crate::svg::get_svg_size(image_data)?;
+        let width_emu = (width_px as f64 * EMU / DPI) as u64;
+        let height_emu = (height_px as f64 * EMU / DPI) as u64;
+        return Ok((width_emu, height_emu));
+    }
+    #[cfg(not(feature = "svg"))]
+    if is_svg(image_data) {
+        return Err(DocxError::NotImage(
+            "svg image, enable the svg feature".to_string(),
+        ));
+    }
+    let img = load_from_memory(image_data)?;
+    let (mut dpi_x, mut dpi_y) = get_image_dpi(image_data).unwrap_or((DPI, DPI));
+    // 照片按EXIF方向旋转90度时交换宽高
+    let (width_px, height_px) = match swaps_dimensions(get_image_orientation(image_data)) {
+        true => {
+            (dpi_x, dpi_y) = (dpi_y, dpi_x);
+            (img.height(), img.width())
+        }
+        false => img.dimensions(),
+    };
+    let width_emu = (width_px as f64 * EMU / dpi_x) as u64;
+    let height_emu = (height_px as f64 * EMU / dpi_y) as u64;
+    Ok((width_emu, height_emu))
+}
+
+/// 读取图片记录的DPI，支持PNG的pHYs、JPEG的JFIF和EXIF以及TIFF
+/// @param image_data 图片数据
+/// @return (x, y) 水平和垂直DPI
+pub fn get_image_dpi(image_data: &[u8]) -> Option<(f64, f64)> {
+    let dpi = if image_data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_dpi(image_data)
+    } else if image_data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_dpi(image_data)
+    } else if image_data.starts_with(b"II*\0") || image_data.starts_with(b"MM\0*") {
+        tiff_dpi(image_data)
+    } else {
+        None
+    };
+    // 忽略明显错误的值
+    dpi.filter(|(x, y)| (1.0..=10000.0).contains(x) && (1.0..=10000.0).contains(y))
+}
+
+/// 读取PNG的pHYs块
+fn png_dpi(image_data: &[u8]) -> Option<(f64, f64)> {
+    let mut offset = 8;
+    while offset + 8 <= image_data.len() {
+        let length = read_u32(image_data, offset, true)? as usize;
+        let chunk_type = &image_data[offset + 4..offset + 8];
+        let data = image_data.get(offset + 8..offset + 8 + length)?;
+        match chunk_type {
+            // 单位为米时换算为英寸
+            b"pHYs" if length == 9 && data[8] == 1 => {
+                let x = read_u32(data, 0, true)? as f64 * 0.0254;
+                let y = read_u32(data, 4, true)? as f64 * 0.0254;
+                return Some((x, y));
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => offset += 12 + length,
+        }
+    }
+    None
+}
+
+/// 读取JPEG的JFIF或EXIF中的DPI
+fn jpeg_dpi(image_data: &[u8]) -> Option<(f64, f64)> {
+    let mut offset = 2;
+    let mut exif_dpi = None;
+    while offset + 4 <= image_data.len() && image_data[offset] == 0xFF {
+        let marker = image_data[offset + 1];
+        // 图像数据开始
+        if marker == 0xDA {
+            break;
+        }
+        let length = read_u16(image_data, offset + 2, true)? as usize;
+        let data = image_data.get(offset + 4..offset + 2 + length)?;
+        match marker {
+            0xE0 if data.starts_with(b"JFIF\0") && data.len() >= 12 => {
+                let x = read_u16(data, 8, true)? as f64;
+                let y = read_u16(data, 10, true)? as f64;
+                match data[7] {
+                    1 => return Some((x, y)),
+                    2 => return Some((x * 2.54, y * 2.54)),
+                    // 只有宽高比，没有DPI
+                    _ => {}
+                }
+            }
+            0xE1 if data.starts_with(b"Exif\0\0") => exif_dpi = tiff_dpi(&data[6..]),
+            _ => {}
+        }
+        offset += 2 + length;
+    }
+    exif_dpi
+}
+
+/// 读取TIFF（EXIF）第一个IFD中的XResolution、YResolution和ResolutionUnit
+fn tiff_dpi(data: &[u8]) -> Option<(f64, f64)> {
+    let big_endian = data.starts_with(b"MM");
+    let ifd = read_u32(data, 4, big_endian)? as usize;
+    let entries = read_u16(data, ifd, big_endian)? as usize;
+    let (mut x, mut y, mut unit) = (None, None, 2);
+    for i in 0..entries {
+        let entry = ifd + 2 + i * 12;
+        let tag = read_u16(data, entry, big_endian)?;
+        let rational = || {
+            let offset = read_u32(data, entry + 8, big_endian)? as usize;
+            let numerator = read_u32(data, offset, big_endian)? as f64;
+            let denominator = read_u32(data, offset + 4, big_endian)? as f64;
+            (denominator > 0.0).then(|| numerator / denominator)
+        };
+        match tag {
+            0x011A => x = rational(),
+            0x011B => y = rational(),
+            0x0128 => unit = read_u16(data, entry + 8, big_endian)?,
+            _ => {}
+        }
+    }
+    let (x, y) = (x?, y.or(x)?);
+    match unit {
+        2 => Some((x, y)),
+        3 => Some((x * 2.54, y * 2.54)),
+        _ => None,
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u16::from_be_bytes(bytes),
+        false => u16::from_le_bytes(bytes),
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    })
+}
+
+/// 读取图片EXIF信息中的方向，没有方向信息时不需要旋转
+/// @param image_data 图片数据
+pub fn get_image_orientation(image_data: &[u8]) -> Orientation {
+    ImageReader::new(Cursor::new(image_data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.orientation().ok())
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+/// 图片方向是否旋转了90度，需要交换宽高
+/// @param orientation 图片方向
+pub(crate) fn swaps_dimensions(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    )
+}
+
 /// 获取本地图片的扩展名
 /// @param image_path 本地图片路径
 /// @return 图片扩展名
@@ -158,3 +479,91 @@ pub fn get_extension(image_path: &str) -> Result<&str, DocxError> {
         .and_then(|s| s.to_str())
         .ok_or_else(|| DocxError::ImageNotFound("Could not determine image extension".to_string()))
 }
+
+/// 根据图片内容的文件头判断图片扩展名
+/// @param image_data 图片数据
+/// @return 图片扩展名
+pub fn guess_extension(image_data: &[u8]) -> Result<&'static str, DocxError> {
+    detect_image_format(image_data)
+        .map(|(ext, _)| ext)
+        .ok_or_else(|| DocxError::NotImage("unknown image format".to_string()))
+}
+
+/// 根据图片内容的文件头判断图片格式
+/// @param image_data 图片数据
+/// @return (ext, content_type) 图片扩展名和内容类型
+pub fn detect_image_format(image_data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if is_svg(image_data) {
+        return Some(("svg", "image/svg+xml"));
+    }
+    let format = image::guess_format(image_data).ok()?;
+    Some((format.extensions_str()[0], format.to_mime_type()))
+}
+
+/// 根据扩展名获取图片内容类型
+/// @param image_ext 图片扩展名
+pub fn content_type_for_extension(image_ext: &str) -> String {
+    match image_ext {
+        "svg" => "image/svg+xml".to_string(),
+        "emf" => "image/x-emf".to_string(),
+        "wmf" => "image/x-wmf".to_string(),
+        ext => match image::ImageFormat::from_extension(ext) {
+            Some(format) => format.to_mime_type().to_string(),
+            None => format!("image/{}", ext),
+        },
+    }
+}
+
+/// 确定图片的扩展名和内容类型，优先根据图片内容判断，无法判断时使用传入的扩展名
+/// @param image_data 图片数据
+/// @param image_ext 传入的扩展名，可以是image/jpeg等内容类型
+fn resolve_format(image_data: &[u8], image_ext: &str) -> (String, String) {
+    if let Some((ext, content_type)) = detect_image_format(image_data) {
+        return (ext.to_string(), content_type.to_string());
+    }
+    // 去掉内容类型前缀和参数，例如 image/jpeg; charset=binary
+    let ext = image_ext.split(';').next().unwrap_or_default().trim();
+    let ext = ext.strip_prefix("image/").unwrap_or(ext);
+    let ext = match ext.to_ascii_lowercase().as_str() {
+        "svg+xml" => "svg".to_string(),
+        "x-emf" => "emf".to_string(),
+        "x-wmf" => "wmf".to_string(),
+        ext => ext.to_string(),
+    };
+    let content_type = content_type_for_extension(&ext);
+    (ext, content_type)
+}
+
+/// 解码data URI或base64字符串格式的图片
+/// @param value 例如 data:image/png;base64,iVBOR... 或 iVBOR...
+/// @return (data, ext) 返回 Vec<u8>和图片扩展名
+pub fn decode_base64_image(value: &str) -> Result<(Vec<u8>, String), DocxError> {
+    let value = value.trim();
+    let (mime, payload) = match value.strip_prefix("data:") {
+        Some(data_uri) => {
+            let (meta, payload) = data_uri
+                .split_once(',')
+                .ok_or_else(|| DocxError::InvalidBase64("missing data".to_string()))?;
+            let mime = meta.strip_suffix(";base64").ok_or_else(|| {
+                DocxError::InvalidBase64(format!("{} is not base64 encoded", meta))
+            })?;
+            (Some(mime), payload)
+        }
+        None => (None, value),
+    };
+    // 忽略换行等空白字符，补位符可有可无
+    let payload: String = payload.chars().filter(|c| !c.is_whitespace()).collect();
+    let engine = GeneralPurpose::new(
+        &STANDARD,
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+    );
+    let image_data = engine
+        .decode(payload)
+        .map_err(|e| DocxError::InvalidBase64(e.to_string()))?;
+    // 优先使用data URI中的类型，否则根据图片内容判断
+    let image_ext = match mime.and_then(|m| m.split(';').next()?.strip_prefix("image/")) {
+        Some(ext) if !ext.is_empty() => ext.to_string(),
+        _ => guess_extension(&image_data)?.to_string(),
+    };
+    Ok((image_data, image_ext))
+}
@@ -1,11 +1,29 @@
+#[cfg(feature = "barcode")]
+pub mod barcode;
+pub mod caption;
 pub mod docx;
 pub mod error;
+pub mod expr;
+#[cfg(feature = "http")]
+pub mod fetcher;
+pub mod format;
+pub mod gallery;
 pub mod image;
+#[cfg(feature = "http")]
+pub mod policy;
+#[cfg(feature = "http")]
 pub mod request;
+pub mod size;
+pub mod style;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod transform;
 #[cfg(test)]
 mod tests {
     use crate::docx::DocxTemplate;
+    use crate::image::DocxImage;
 
+    #[cfg(feature = "http")]
     #[tokio::test] // 使用 tokio 运行时
     async fn test_replacement() {
         // 1. 创建模板处理器
@@ -34,4 +52,1000 @@ mod tests {
 
         println!("文档生成成功!");
     }
+
+    /// 创建只包含一个段落的最小docx模板
+    fn minimal_docx(body: &str) -> Vec<u8> {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut zip_writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let files = [
+            (
+                "[Content_Types].xml",
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/></Types>"#.to_string(),
+            ),
+            (
+                "_rels/.rels",
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#.to_string(),
+            ),
+            (
+                "word/document.xml",
+                format!(
+                    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing"><w:body>{}<w:sectPr><w:pgSz w:w="11906" w:h="16838"/><w:pgMar w:top="1440" w:right="1800" w:bottom="1440" w:left="1800"/></w:sectPr></w:body></w:document>"#,
+                    body
+                ),
+            ),
+            (
+                "word/_rels/document.xml.rels",
+                r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"></Relationships>"#.to_string(),
+            ),
+        ];
+        for (name, contents) in files {
+            zip_writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            zip_writer.write_all(contents.as_bytes()).unwrap();
+        }
+        zip_writer.finish().unwrap().into_inner()
+    }
+
+    /// 读取生成文档中的文件内容
+    fn read_part(docx: &[u8], name: &str) -> String {
+        use std::io::Read;
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(docx)).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_compiled_template_render() {
+        use crate::docx::CompiledTemplate;
+
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:r><w:t>{{name}}: {{qty * price|rmb_upper}}</w:t></w:r></w:p><w:p><w:r><w:t>{{na</w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>me}}</w:t></w:r><w:r><w:t xml:space="preserve"> &amp; {{</w:t></w:r></w:p>"#,
+        ))
+        .unwrap();
+        let mut docx_template = DocxTemplate::new();
+        docx_template.add_text_replacement("{{name}}", "合计");
+        docx_template.add_text_replacement("{{qty}}", "2");
+        docx_template.add_text_replacement("{{price}}", "5000");
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        assert!(document.contains("合计: 壹万元整"));
+        // 拆分到多个文本块的占位符合并后写入最后一个文本块，没有结束标记的文本原样写入
+        assert!(document.contains("<w:p><w:r><w:t>合计</w:t></w:r>"));
+        assert!(document.contains(r#"<w:t xml:space="preserve"> &amp; {{</w:t>"#));
+    }
+
+    #[test]
+    fn test_render_batch() {
+        use crate::docx::{BatchOptions, BatchOutput, CompiledTemplate};
+
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{name}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let records = (1..=6).map(|id| {
+            let mut record = DocxTemplate::new();
+            record.add_text_replacement("{{id}}", &id.to_string());
+            let name = match id {
+                5 => "张三",
+                6 => "李_四_4",
+                _ => "李/四",
+            };
+            record.add_text_replacement("{{name}}", name);
+            record
+        });
+        let output = std::env::temp_dir().join(format!("batch_{}.zip", uuid::Uuid::new_v4()));
+        let report = template
+            .render_batch(
+                records,
+                &BatchOutput::Zip(output.clone()),
+                &BatchOptions::new("{{id < 3 ? 0 : 1}}_{{name}}.docx").with_threads(3),
+            )
+            .unwrap();
+        assert_eq!(report.files.len(), 6);
+        assert!(report.errors.is_empty());
+        assert!(report.files.contains(&"1_张三.docx".to_string()));
+        // 按记录顺序输出，重名后缀按记录顺序分配，追加后缀后仍重复时继续递增
+        let expected = [
+            "0_李_四.docx",
+            "0_李_四_2.docx",
+            "1_李_四.docx",
+            "1_李_四_4.docx",
+            "1_张三.docx",
+            "1_李_四_4_6.docx",
+        ];
+        assert_eq!(report.files, expected);
+        let archive = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+        assert_eq!(archive.len(), 6);
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), expected);
+        std::fs::remove_file(output).unwrap();
+
+        // 输出到目录时默认不覆盖已存在的文件
+        let output = std::env::temp_dir().join(format!("batch_{}", uuid::Uuid::new_v4()));
+        let record = || {
+            let mut record = DocxTemplate::new();
+            record.add_text_replacement("{{name}}", "张三");
+            record
+        };
+        let options = BatchOptions::new("{{name}}.docx");
+        let directory = BatchOutput::Directory(output.clone());
+        let report = template
+            .render_batch([record()], &directory, &options)
+            .unwrap();
+        assert_eq!(report.files, ["张三.docx"]);
+        let report = template
+            .render_batch([record()], &directory, &options)
+            .unwrap();
+        assert_eq!(report.errors.len(), 1);
+        let options = options.with_overwrite(true);
+        let report = template
+            .render_batch([record()], &directory, &options)
+            .unwrap();
+        assert!(report.errors.is_empty());
+        std::fs::remove_dir_all(output).unwrap();
+    }
+
+    #[test]
+    fn test_render_merged() {
+        use crate::docx::{CompiledTemplate, MergeSeparator};
+
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:bookmarkStart w:id="0" w:name="title"/><w:r><w:t>{{name}}</w:t></w:r><w:bookmarkEnd w:id="0"/></w:p><w:p><w:hyperlink w:anchor="title"><w:r><w:t>目录</w:t></w:r></w:hyperlink><w:fldSimple w:instr=" REF title \h "/><w:r><w:instrText xml:space="preserve"> PAGEREF title \h </w:instrText></w:r></w:p>"#,
+        ))
+        .unwrap();
+        let records: Vec<DocxTemplate> = ["张三", "李四"]
+            .iter()
+            .map(|name| {
+                let mut record = DocxTemplate::new();
+                record.add_text_replacement("{{name}}", name);
+                record
+            })
+            .collect();
+        let docx = template
+            .render_merged(&records, MergeSeparator::SectionBreak)
+            .unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        assert!(document.contains("张三") && document.contains("李四"));
+        assert_eq!(document.matches("<w:body>").count(), 1);
+        assert_eq!(document.matches("<w:sectPr>").count(), 2);
+        assert!(document.contains(r#"w:id="1" w:name="title_2""#));
+        // 超链接和域代码引用的书签名称随书签一起替换
+        assert!(document.contains(r#"w:anchor="title_2""#));
+        assert!(document.contains(r#"w:instr=" REF title_2 \h ""#));
+        assert!(document.contains(r#"> PAGEREF title_2 \h </w:instrText>"#));
+        assert!(document.contains(r#"w:anchor="title""#));
+        let empty: [&DocxTemplate; 0] = [];
+        assert!(
+            template
+                .render_merged(empty, MergeSeparator::PageBreak)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_content_hash_dedup() {
+        use crate::docx::{CompiledTemplate, MergeSeparator};
+        use crate::gallery::ImageGallery;
+
+        // 文本占位符、图片组和模板中已有的图片使用相同的图片内容
+        let record = || {
+            let mut record = DocxTemplate::new();
+            record
+                .add_image_bytes_replacement("{{logo}}", png_bytes(8, 8), None)
+                .unwrap();
+            record
+                .add_image_bytes_replacement("{{photo}}", png_bytes(8, 8), Some((1.0, 1.0)))
+                .unwrap();
+            let image = DocxImage::new_image_data("logo", png_bytes(8, 8), "png").unwrap();
+            record.add_image_gallery_replacement(
+                "{{photos}}",
+                ImageGallery::grid(2).add_image(image),
+            );
+            record
+        };
+        let docx_template = record();
+        assert_eq!(docx_template.images().count(), 1);
+        let relation_id = docx_template.images().next().unwrap().relation_id.clone();
+
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:r><w:t>{{logo}}</w:t></w:r></w:p><w:p><w:r><w:t>{{photos}}</w:t></w:r></w:p><w:p><w:r><w:drawing><wp:inline><wp:docPr id="1" name="Sample" descr="{{photo}}"/><a:graphic><a:blip r:embed="rId9"/></a:graphic></wp:inline></w:drawing></w:r></w:p>"#,
+        ))
+        .unwrap();
+        let docx = template.render(&docx_template).unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        let embed = format!(r#"r:embed="{}""#, relation_id);
+        assert_eq!(document.matches(&embed).count(), 3);
+        let rels = read_part(&docx, "word/_rels/document.xml.rels");
+        assert_eq!(rels.matches("media/image_").count(), 1);
+
+        // 合并文档时每条记录的相同图片只保存一份
+        let records = [docx_template, record()];
+        let docx = template
+            .render_merged(&records, MergeSeparator::PageBreak)
+            .unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        assert_eq!(document.matches(&embed).count(), 6);
+        let rels = read_part(&docx, "word/_rels/document.xml.rels");
+        assert_eq!(rels.matches("media/image_").count(), 1);
+    }
+
+    /// 创建指定像素大小的png图片
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut data = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(width, height)
+            .write_to(&mut data, image::ImageFormat::Png)
+            .unwrap();
+        data.into_inner()
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_image_fetcher() {
+        use crate::docx::CompiledTemplate;
+        use crate::fetcher::{FetchFuture, ImageFetcher};
+
+        struct StubFetcher;
+        impl ImageFetcher for StubFetcher {
+            fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+                Box::pin(async move {
+                    assert_eq!(url, "s3://bucket/photo");
+                    Ok((png_bytes(96, 48), "png".to_string()))
+                })
+            }
+        }
+
+        let mut docx_template = DocxTemplate::new();
+        docx_template.set_image_fetcher(StubFetcher);
+        docx_template
+            .add_image_url_replacement("{{photo}}", Some("s3://bucket/photo"))
+            .await
+            .unwrap();
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{photo}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let docx = template.render(&docx_template).unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        assert!(document.contains(r#"<wp:extent cx="914400" cy="457200"/>"#));
+        assert!(read_part(&docx, "word/_rels/document.xml.rels").contains("media/image_"));
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_add_image_url_replacements() {
+        use crate::fetcher::{FetchFuture, ImageFetcher};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingFetcher(AtomicUsize);
+        impl ImageFetcher for CountingFetcher {
+            fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move {
+                    match url {
+                        "bad" => Err(crate::error::DocxError::NotImage(url.to_string())),
+                        "two" => Ok((png_bytes(8, 4), "png".to_string())),
+                        _ => Ok((png_bytes(8, 8), "png".to_string())),
+                    }
+                })
+            }
+        }
+
+        let fetcher = std::sync::Arc::new(CountingFetcher(AtomicUsize::new(0)));
+        let mut docx_template = DocxTemplate::new();
+        docx_template.set_image_fetcher(fetcher.clone());
+        let result = docx_template
+            .add_image_url_replacements(
+                [
+                    ("{{a}}", Some("one")),
+                    ("{{b}}", Some("two")),
+                    ("{{c}}", Some("one")),
+                    ("{{d}}", Some("bad")),
+                    ("{{e}}", None),
+                ],
+                2,
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(fetcher.0.load(Ordering::SeqCst), 3);
+        assert_eq!(docx_template.images().count(), 2);
+
+        // 获取失败时使用占位图片
+        let mut docx_template = DocxTemplate::new();
+        docx_template.set_image_fetcher(fetcher.clone());
+        let fallback = DocxImage::new_image_data("fallback", png_bytes(4, 4), "png").unwrap();
+        docx_template
+            .set_image_fallback(crate::image::ImageFallback::Placeholder(Box::new(fallback)));
+        docx_template
+            .add_image_url_replacements([("{{a}}", Some("bad")), ("{{b}}", Some("bad"))], 2)
+            .await
+            .unwrap();
+        docx_template
+            .add_image_url_replacement("{{c}}", Some("bad"))
+            .await
+            .unwrap();
+        assert_eq!(docx_template.images().count(), 1);
+    }
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_url_policy() {
+        use crate::error::DocxError;
+        use crate::fetcher::ImageFetcher;
+        use crate::policy::UrlPolicy;
+        use crate::request::HttpImageFetcher;
+
+        let policy = UrlPolicy::new().allow_host("*.example.com");
+        assert!(policy.check_url("https://img.example.com/a.png").is_ok());
+        assert!(
+            policy
+                .check_url("https://example.com.evil.net/a.png")
+                .is_err()
+        );
+        assert!(policy.check_url("file:///etc/passwd").is_err());
+        let policy = UrlPolicy::new().deny_host("evil.net");
+        assert!(policy.check_url("http://evil.net/a.png").is_err());
+        assert!(policy.check_url("http://10.0.0.1/a.png").is_err());
+        assert!(policy.check_url("http://[::ffff:127.0.0.1]/a.png").is_err());
+        assert!(policy.check_url("http://[::127.0.0.1]/a.png").is_err());
+        assert!(
+            policy
+                .check_url("http://[64:ff9b::10.0.0.1]/a.png")
+                .is_err()
+        );
+        assert!(policy.check_url("http://[2002:c0a8:101::1]/a.png").is_err());
+        assert!(policy.check_url("http://[2002:808:808::1]/a.png").is_ok());
+        assert!(policy.check_url("http://169.254.169.254/latest").is_err());
+        for url in [
+            "http://224.0.0.1/a.png",
+            "http://192.0.0.8/a.png",
+            "http://198.19.0.1/a.png",
+            "http://240.0.0.1/a.png",
+            "http://[ff02::1]/a.png",
+            "http://[fec0::1]/a.png",
+        ] {
+            assert!(policy.check_url(url).is_err(), "{}", url);
+        }
+        assert!(policy.check_url("http://198.20.0.1/a.png").is_ok());
+        assert!(policy.check_url("http://8.8.8.8/a.png").is_ok());
+
+        // 域名解析到回环地址时拒绝
+        let fetcher = HttpImageFetcher::builder()
+            .url_policy(UrlPolicy::new())
+            .build()
+            .unwrap();
+        let result = fetcher.fetch("http://localhost:9/a.png").await;
+        assert!(matches!(result, Err(DocxError::UrlNotAllowed(_))));
+    }
+
+    #[test]
+    fn test_memory_image() {
+        use crate::docx::CompiledTemplate;
+        use base64::Engine;
+
+        let encoded = base64::engine::general_purpose::STANDARD.encode(png_bytes(96, 96));
+        let padded = base64::engine::general_purpose::STANDARD.encode(png_bytes(32, 32));
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_base64_replacement(
+                "{{sign}}",
+                Some(&format!("data:image/png;base64,{}", encoded)),
+            )
+            .unwrap();
+        docx_template
+            .add_image_base64_size_replacement(
+                "{{raw}}",
+                Some(padded.trim_end_matches('=')),
+                2.0,
+                1.0,
+            )
+            .unwrap();
+        assert!(
+            docx_template
+                .add_image_base64_replacement("{{bad}}", Some("data:image/png;base64,@@"))
+                .is_err()
+        );
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{sign}}</w:t></w:r></w:p><w:p><w:r><w:t>{{raw}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let docx = template.render(&docx_template).unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        assert!(document.contains(r#"<wp:extent cx="914400" cy="914400"/>"#));
+        assert!(document.contains(r#"<wp:extent cx="720000" cy="360000"/>"#));
+        assert_eq!(docx_template.images().count(), 2);
+
+        // 内存图片数据
+        docx_template
+            .add_image_bytes_replacement("{{raw}}", png_bytes(48, 48), None)
+            .unwrap();
+        assert!(
+            docx_template
+                .add_image_bytes_replacement(
+                    "{{chart}}",
+                    b"not an image".as_slice(),
+                    Some((1.0, 1.0))
+                )
+                .is_err()
+        );
+        let docx = template.render(&docx_template).unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        assert!(document.contains(r#"<wp:extent cx="457200" cy="457200"/>"#));
+        assert_eq!(docx_template.images().count(), 2);
+
+        // 根据图片内容判断格式，并补充内容类型声明
+        let image = DocxImage::new_image_data("photo.jpg", png_bytes(4, 4), "jpeg; q=1").unwrap();
+        assert_eq!(
+            (image.image_ext.as_str(), image.content_type.as_str()),
+            ("png", "image/png")
+        );
+        let content_types = read_part(&docx, "[Content_Types].xml");
+        assert_eq!(
+            content_types
+                .matches(r#"<Default Extension="png" ContentType="image/png"/>"#)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_image_options() {
+        use crate::size::{ImageSize, Length};
+        use crate::transform::ImageOptions;
+
+        let mut webp = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(16, 8)
+            .write_to(&mut webp, image::ImageFormat::WebP)
+            .unwrap();
+        let webp = webp.into_inner();
+
+        // 默认将WebP转换为JPEG
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_bytes_replacement("{{photo}}", webp.clone(), None)
+            .unwrap();
+        let image = docx_template.images().next().unwrap();
+        assert_eq!(
+            (image.image_ext.as_str(), image.content_type.as_str()),
+            ("jpg", "image/jpeg")
+        );
+        assert_eq!(
+            image::guess_format(&image.image_data).unwrap(),
+            image::ImageFormat::Jpeg
+        );
+
+        // 关闭格式转换
+        let mut docx_template = DocxTemplate::new();
+        docx_template.set_image_options(ImageOptions {
+            convert_unsupported: false,
+            ..ImageOptions::default()
+        });
+        docx_template
+            .add_image_bytes_replacement("{{photo}}", webp, None)
+            .unwrap();
+        assert_eq!(docx_template.images().next().unwrap().image_ext, "webp");
+
+        // 写入文档时按实际显示大小和目标DPI缩小图片，100DPI
+        let mut docx_template = DocxTemplate::new();
+        docx_template.set_image_options(ImageOptions {
+            target_dpi: Some(100),
+            ..ImageOptions::default()
+        });
+        // 1英寸显示，之后改为2英寸时按2英寸缩小
+        docx_template
+            .add_image_bytes_replacement("{{photo}}", png_bytes(400, 200), Some((2.54, 1.27)))
+            .unwrap();
+        docx_template
+            .set_image_size("{{photo}}", ImageSize::Width(Length::Inch(2.0)))
+            .unwrap();
+        // 默认大小的图片按页面可用宽度14.65厘米缩小
+        docx_template
+            .add_image_bytes_replacement("{{wide}}", png_bytes(3000, 100), None)
+            .unwrap();
+        // 裁剪的图片按整张图片的显示大小缩小，1英寸的整张图片保留100像素
+        docx_template
+            .add_image_bytes_replacement("{{crop}}", png_bytes(401, 200), Some((2.54, 1.27)))
+            .unwrap();
+        let style = crate::style::ImageStyle::new().crop(25.0, 25.0, 25.0, 25.0);
+        docx_template.set_image_style("{{crop}}", style).unwrap();
+        let template = crate::docx::CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{photo}}</w:t></w:r></w:p><w:p><w:r><w:t>{{wide}}</w:t></w:r></w:p><w:p><w:r><w:t>{{crop}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let docx = template.render(&docx_template).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(docx)).unwrap();
+        let mut widths = Vec::new();
+        for image in docx_template.images() {
+            let name = format!("word/media/image_{}.png", image.relation_id);
+            let mut image_data = Vec::new();
+            std::io::Read::read_to_end(&mut archive.by_name(&name).unwrap(), &mut image_data)
+                .unwrap();
+            widths.push(image::load_from_memory(&image_data).unwrap().width());
+        }
+        widths.sort();
+        assert_eq!(widths, [100, 200, 577]);
+    }
+
+    #[test]
+    fn test_exif_orientation() {
+        // 40x20的JPEG照片，EXIF方向为顺时针旋转90度
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(40, 20)
+            .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+        let jpeg = jpeg.into_inner();
+        let tiff: &[u8] = &[
+            b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0,
+            0,
+        ];
+        let mut exif = b"Exif\0\0".to_vec();
+        exif.extend_from_slice(tiff);
+        let mut photo = jpeg[..2].to_vec();
+        photo.extend_from_slice(&[0xFF, 0xE1]);
+        photo.extend_from_slice(&((exif.len() + 2) as u16).to_be_bytes());
+        photo.extend_from_slice(&exif);
+        photo.extend_from_slice(&jpeg[2..]);
+
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_bytes_replacement("{{photo}}", photo, None)
+            .unwrap();
+        let image = docx_template.images().next().unwrap();
+        assert_eq!((image.width, image.height), (190500, 381000));
+        let pixels = image::load_from_memory(&image.image_data).unwrap();
+        assert_eq!((pixels.width(), pixels.height()), (20, 40));
+    }
+
+    #[test]
+    fn test_default_image_size() {
+        use crate::docx::CompiledTemplate;
+        use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+
+        // 按JFIF中的DPI计算大小：96像素，192DPI
+        let mut jpeg = Vec::new();
+        let mut encoder = JpegEncoder::new(&mut jpeg);
+        encoder.set_pixel_density(PixelDensity::dpi(192));
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(96, 48))
+            .write_with_encoder(encoder)
+            .unwrap();
+        assert_eq!(crate::image::get_image_dpi(&jpeg), Some((192.0, 192.0)));
+
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_bytes_replacement("{{dpi}}", jpeg, None)
+            .unwrap();
+        docx_template
+            .add_image_bytes_replacement("{{wide}}", png_bytes(1000, 100), None)
+            .unwrap();
+        docx_template
+            .add_image_bytes_replacement("{{cell}}", png_bytes(1000, 100), None)
+            .unwrap();
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:r><w:t>{{dpi}}</w:t></w:r></w:p><w:p><w:r><w:t>{{wide}}</w:t></w:r></w:p><w:tbl><w:tr><w:tc><w:tcPr><w:tcW w:w="2376" w:type="dxa"/></w:tcPr><w:p><w:r><w:t>{{cell}}</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#,
+        ))
+        .unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        assert!(document.contains(r#"<wp:extent cx="457200" cy="228600"/>"#));
+        // 页面宽度11906减去左右页边距1800
+        assert!(document.contains(r#"<wp:extent cx="5274310" cy="527431"/>"#));
+        // 单元格宽度2376减去单元格边距
+        assert!(document.contains(r#"<wp:extent cx="1371600" cy="137160"/>"#));
+    }
+
+    #[test]
+    fn test_image_size() {
+        use crate::docx::CompiledTemplate;
+        use crate::size::{ImageSize, Length};
+
+        let mut docx_template = DocxTemplate::new();
+        for placeholder in ["{{contain}}", "{{cover}}", "{{width}}"] {
+            docx_template
+                .add_image_bytes_replacement(placeholder, png_bytes(200, 100), None)
+                .unwrap();
+        }
+        docx_template
+            .set_image_size(
+                "{{contain}}",
+                ImageSize::Contain(Length::Inch(1.0), Length::Inch(1.0)),
+            )
+            .unwrap();
+        docx_template
+            .set_image_size(
+                "{{cover}}",
+                ImageSize::Cover(Length::Cm(2.0), Length::Mm(20.0)),
+            )
+            .unwrap();
+        docx_template
+            .set_image_size("{{width}}", ImageSize::Width(Length::Pt(72.0)))
+            .unwrap();
+        assert!(
+            docx_template
+                .set_image_size("{{missing}}", ImageSize::Width(Length::Px(10.0)))
+                .is_err()
+        );
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{contain}}</w:t></w:r></w:p><w:p><w:r><w:t>{{cover}}</w:t></w:r></w:p><w:p><w:r><w:t>{{width}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        assert_eq!(
+            document
+                .matches(r#"<wp:extent cx="914400" cy="457200"/>"#)
+                .count(),
+            2
+        );
+        assert!(document.contains(r#"<wp:extent cx="720000" cy="720000"/>"#));
+        assert!(document.contains(r#"<a:srcRect l="25000" t="0" r="25000" b="0"/>"#));
+    }
+
+    #[test]
+    fn test_swap_image() {
+        use crate::docx::CompiledTemplate;
+
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_bytes_replacement("{{photo}}", png_bytes(8, 8), None)
+            .unwrap();
+        docx_template
+            .add_image_file_replacement("{{blank}}", None)
+            .unwrap();
+        let relation_id = docx_template.images().next().unwrap().relation_id.clone();
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:r><w:drawing><wp:inline><wp:extent cx="123" cy="456"/><wp:docPr id="1" name="Sample" descr="{{photo}}"/><a:graphic><a:graphicData><pic:pic><pic:blipFill><a:blip r:embed="rId9"/><a:srcRect l="10"/></pic:blipFill></pic:pic></a:graphicData></a:graphic></wp:inline></w:drawing></w:r><w:r><w:drawing><wp:inline><wp:docPr id="2" name="Other" title="{{blank}}"/><a:graphic><a:blip r:embed="rId8"/></a:graphic></wp:inline></w:drawing></w:r></w:p>"#,
+        ))
+        .unwrap();
+        let docx = template.render(&docx_template).unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        assert!(document.contains(&format!(
+            r#"<a:blip r:embed="{}"/><a:srcRect l="10"/>"#,
+            relation_id
+        )));
+        assert!(document.contains(r#"<wp:extent cx="123" cy="456"/>"#));
+        assert!(document.contains(r#"<wp:docPr id="1" name="Sample"/>"#));
+        // 图片为空时保留原图片，去掉占位符
+        assert!(document.contains(r#"<wp:docPr id="2" name="Other"/>"#));
+        assert!(document.contains(r#"<a:blip r:embed="rId8"/>"#));
+        assert!(read_part(&docx, "word/_rels/document.xml.rels").contains(&relation_id));
+
+        // 可选文字中不支持图片组
+        docx_template.add_image_gallery_replacement(
+            "{{blank}}",
+            crate::gallery::ImageGallery::grid(2)
+                .add_image(DocxImage::new_image_data("a", png_bytes(8, 8), "png").unwrap()),
+        );
+        assert!(template.render(&docx_template).is_err());
+    }
+
+    #[test]
+    fn test_image_style() {
+        use crate::docx::CompiledTemplate;
+        use crate::size::Length;
+        use crate::style::{ImageStyle, Shadow};
+
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_bytes_replacement("{{photo}}", png_bytes(96, 48), None)
+            .unwrap();
+        let style = ImageStyle::new()
+            .rotate(90.0)
+            .flip_horizontal()
+            .crop(10.0, 0.0, 10.0, 0.0)
+            .outline("FF0000", Length::Pt(1.0))
+            .shadow(Shadow::default());
+        docx_template.set_image_style("{{photo}}", style).unwrap();
+        let invalid = ImageStyle::new().outline("red\"/>", Length::Pt(1.0));
+        assert!(docx_template.set_image_style("{{photo}}", invalid).is_err());
+
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{photo}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        // 裁剪后显示宽度为原图片的80%
+        assert!(document.contains(r#"<wp:extent cx="731520" cy="457200"/>"#));
+        assert!(document.contains(r#"<wp:effectExtent l="6350" t="143510" r="6350" b="143510"/>"#));
+        assert!(document.contains(r#"<a:srcRect l="10000" t="0" r="10000" b="0"/>"#));
+        assert!(document.contains(r#"<a:xfrm rot="5400000" flipH="1">"#));
+        assert!(document.contains(r#"<a:ln w="12700"><a:solidFill><a:srgbClr val="FF0000"/>"#));
+        assert!(document.contains("<a:outerShdw"));
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn test_svg_image() {
+        use crate::docx::CompiledTemplate;
+        use crate::image::get_image_size;
+
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="96" height="48"><rect width="96" height="48" fill="#F00"/></svg>"##;
+        assert_eq!(get_image_size(svg).unwrap(), (914400, 457200));
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_bytes_replacement("{{logo}}", svg.to_vec(), None)
+            .unwrap();
+        let docx_image = docx_template.images().next().unwrap();
+        assert_eq!(docx_image.image_ext, "png");
+        assert_eq!(
+            image::load_from_memory(&docx_image.image_data)
+                .unwrap()
+                .width(),
+            192
+        );
+        let svg_relation_id = docx_image.svg_relation_id();
+
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{logo}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let docx = template.render(&docx_template).unwrap();
+        let document = read_part(&docx, "word/document.xml");
+        assert!(document.contains(&format!(r#"<asvg:svgBlip xmlns:asvg="http://schemas.microsoft.com/office/drawing/2016/SVG/main" r:embed="{}"/>"#, svg_relation_id)));
+        let rels = read_part(&docx, "word/_rels/document.xml.rels");
+        assert!(rels.contains(&format!("media/image_{}.svg", svg_relation_id)));
+        assert!(read_part(&docx, "[Content_Types].xml").contains("image/svg+xml"));
+        assert!(
+            read_part(&docx, &format!("word/media/image_{}.svg", svg_relation_id)).contains("<svg")
+        );
+
+        // 模板中已有图片的a:blip带有扩展列表时，SVG引用加入已有的扩展列表
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:r><w:drawing><wp:inline><wp:extent cx="123" cy="456"/><wp:docPr id="1" name="Logo" title="{{logo}}"/><a:graphic><a:blip r:embed="rId9"><a:extLst><a:ext uri="{28A0092B-C50C-407E-A947-70E740481C1C}"><a14:useLocalDpi val="0"/></a:ext></a:extLst></a:blip></a:graphic></wp:inline></w:drawing></w:r></w:p>"#,
+        ))
+        .unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        assert!(document.contains(&format!(r#"<a14:useLocalDpi val="0"/></a:ext><a:ext uri="{{96DAC541-7B7A-43D3-8B79-37D633B846F1}}"><asvg:svgBlip xmlns:asvg="http://schemas.microsoft.com/office/drawing/2016/SVG/main" r:embed="{}"/></a:ext></a:extLst></a:blip>"#, svg_relation_id)));
+        assert!(!document.contains("{{logo}}"));
+    }
+
+    #[test]
+    fn test_image_gallery() {
+        use crate::docx::CompiledTemplate;
+        use crate::gallery::ImageGallery;
+        use crate::size::Length;
+
+        let photo = DocxImage::new_image_data("photo.png", png_bytes(960, 480), "png").unwrap();
+        let mut docx_template = DocxTemplate::new();
+        docx_template.add_image_gallery_replacement(
+            "{{photos}}",
+            ImageGallery::grid(2)
+                .add_image_caption(photo.clone(), "东侧 <1>")
+                .add_image(photo.clone())
+                .add_image(DocxImage::new_image_data("b.png", png_bytes(48, 48), "png").unwrap()),
+        );
+        docx_template.add_image_gallery_replacement(
+            "{{row}}",
+            ImageGallery::inline(Length::Pt(6.0))
+                .add_image(photo.clone())
+                .add_image(photo),
+        );
+        // 同一图片只写入一次
+        assert_eq!(docx_template.images().count(), 2);
+
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            "<w:p><w:r><w:t>{{photos}}</w:t></w:r></w:p><w:p><w:r><w:t>{{row}}</w:t></w:r></w:p>",
+        ))
+        .unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        // 页面可用宽度8306缇，每列4153缇，图片宽度按单元格宽度缩小
+        assert!(document.contains(r#"</w:p><w:tbl>"#));
+        assert_eq!(document.matches(r#"<w:gridCol w:w="4153"/>"#).count(), 2);
+        assert_eq!(document.matches("<w:tc>").count(), 4);
+        assert_eq!(
+            document
+                .matches(r#"<wp:extent cx="2499995" cy="1249997"/>"#)
+                .count(),
+            2
+        );
+        assert!(document.contains("东侧 &lt;1&gt;"));
+        assert!(document.contains(r#"</w:tbl><w:p>"#));
+        // 行内排列，图片之间为加宽的空格
+        assert!(document.contains(r#"<w:spacing w:val="120"/>"#));
+        assert_eq!(document.matches(r#"<wp:extent cx="5274310""#).count(), 2);
+    }
+
+    #[test]
+    fn test_image_caption() {
+        use crate::caption::ImageCaption;
+        use crate::docx::CompiledTemplate;
+        use std::io::Write;
+
+        let mut docx_template = DocxTemplate::new();
+        docx_template
+            .add_image_bytes_replacement("{{chart}}", png_bytes(96, 48), None)
+            .unwrap();
+        docx_template
+            .set_image_caption("{{chart}}", ImageCaption::new("销售趋势").label("图"))
+            .unwrap();
+        let invalid = ImageCaption::new("销售趋势").label("图 1");
+        assert!(
+            docx_template
+                .set_image_caption("{{chart}}", invalid)
+                .is_err()
+        );
+
+        // 模板中已有一个图题注，题注样式编号为a5
+        let mut zip_writer = zip::ZipWriter::new_append(std::io::Cursor::new(minimal_docx(
+            r#"<w:p><w:r><w:instrText xml:space="preserve"> SEQ 图 \* ARABIC </w:instrText></w:r></w:p><w:p><w:r><w:t>{{chart}}</w:t></w:r></w:p>"#,
+        )))
+        .unwrap();
+        zip_writer
+            .start_file("word/styles.xml", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip_writer
+            .write_all(br#"<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:style w:type="paragraph" w:styleId="a5"><w:name w:val="caption"/></w:style></w:styles>"#)
+            .unwrap();
+        let template =
+            CompiledTemplate::from_bytes(&zip_writer.finish().unwrap().into_inner()).unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        assert!(document.contains(r#"</w:drawing></w:p><w:p><w:pPr><w:pStyle w:val="a5"/></w:pPr><w:r><w:t xml:space="preserve">图 </w:t></w:r><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText xml:space="preserve"> SEQ 图 \* ARABIC </w:instrText></w:r><w:r><w:fldChar w:fldCharType="separate"/></w:r><w:r><w:t xml:space="preserve">2</w:t></w:r><w:r><w:fldChar w:fldCharType="end"/></w:r><w:r><w:t xml:space="preserve"> 销售趋势</w:t></w:r></w:p>"#));
+
+        // 只统计域代码中的SEQ域，包括w:fldSimple，正文中的文字不计入
+        let template = CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:r><w:t>SEQ 图 表示图片序号</w:t></w:r></w:p><w:p><w:fldSimple w:instr=" SEQ 图 \* ARABIC "><w:r><w:t>1</w:t></w:r></w:fldSimple></w:p><w:p><w:fldSimple w:instr=" SEQ 图 \* ARABIC "><w:r><w:t>2</w:t></w:r></w:fldSimple></w:p><w:p><w:r><w:t>{{chart}}</w:t></w:r></w:p>"#,
+        ))
+        .unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        assert!(document.contains(r#"<w:r><w:fldChar w:fldCharType="separate"/></w:r><w:r><w:t xml:space="preserve">3</w:t></w:r>"#));
+
+        // 合并文档时所有记录的题注连续编号
+        let document = read_part(
+            &template
+                .render_merged(
+                    [&docx_template, &docx_template],
+                    crate::docx::MergeSeparator::PageBreak,
+                )
+                .unwrap(),
+            "word/document.xml",
+        );
+        assert!(document.contains(r#"<w:r><w:fldChar w:fldCharType="separate"/></w:r><w:r><w:t xml:space="preserve">6</w:t></w:r>"#));
+    }
+
+    #[cfg(feature = "barcode")]
+    #[test]
+    fn test_barcode() {
+        use crate::barcode::Barcode;
+        use crate::size::Length;
+
+        // 按像素读取条形码的条和空，宽度较小时每个模块为1像素
+        let modules = |barcode: Barcode| {
+            let png = barcode.to_png(1, 1).unwrap();
+            let img = image::load_from_memory(&png).unwrap().to_luma8();
+            (0..img.width())
+                .map(|x| {
+                    if img.get_pixel(x, 0)[0] == 0 {
+                        '1'
+                    } else {
+                        '0'
+                    }
+                })
+                .collect::<String>()
+        };
+        // 起始字符B、校验字符55、终止字符
+        let code128 = modules(Barcode::Code128("PJJ123C".to_string()));
+        assert_eq!(code128.len(), 112 + 20);
+        assert!(code128[10..].starts_with("11010010000"));
+        assert!(code128[..122].ends_with("111010001101100011101011"));
+        // 校验位为1
+        let ean13 = modules(Barcode::Ean13("400638133393".to_string()));
+        assert_eq!(ean13.len(), 95 + 20);
+        assert!(ean13[10..105].ends_with("1100110101"));
+        assert!(
+            Barcode::Ean13("4006381333932".to_string())
+                .to_png(1, 1)
+                .is_err()
+        );
+        assert!(Barcode::Code128("中文".to_string()).to_png(1, 1).is_err());
+
+        let mut docx_template = DocxTemplate::new();
+        let qr = Barcode::QrCode("https://example.com/verify?id=1".to_string());
+        docx_template
+            .add_barcode_replacement("{{qr}}", &qr, Length::Cm(2.0), Length::Cm(2.0))
+            .unwrap();
+        let docx_image = docx_template.images().next().unwrap();
+        assert_eq!((docx_image.width, docx_image.height), (720000, 720000));
+        let img = image::load_from_memory(&docx_image.image_data).unwrap();
+        assert!(img.width() >= 236 && img.width() == img.height());
+    }
+
+    #[test]
+    fn test_compiled_template_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<crate::docx::CompiledTemplate>();
+        assert_send_sync::<DocxTemplate>();
+    }
+
+    #[test]
+    fn test_format() {
+        use crate::format::*;
+
+        assert_eq!(to_rmb_upper("¥10,000").unwrap(), "壹万元整");
+        assert_eq!(to_rmb_upper("100010000.05").unwrap(), "壹亿零壹万元零伍分");
+        assert_eq!(to_rmb_upper("0.5").unwrap(), "伍角");
+        assert_eq!(to_chinese_upper("1200000000000").unwrap(), "壹万贰仟亿");
+        assert_eq!(to_chinese_date("2023-11-25").unwrap(), "2023年11月25日");
+        assert_eq!(
+            to_chinese_date_upper("2023/11/25").unwrap(),
+            "二〇二三年十一月二十五日"
+        );
+        assert_eq!(to_full_width_digits("No.2023"), "No.２０２３");
+        assert!(apply_filter("unknown", "1").is_err());
+    }
+
+    #[test]
+    fn test_expression() {
+        use crate::expr::{Value, evaluate};
+
+        let lookup = |name: &str| match name {
+            "qty" => Some("3".to_string()),
+            "price" => Some("1,200.5".to_string()),
+            "a" => Some("yes".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            evaluate("qty * price", lookup).unwrap().to_string(),
+            "3601.5"
+        );
+        assert_eq!(evaluate("a == \"yes\"", lookup).unwrap(), Value::Bool(true));
+        assert_eq!(
+            evaluate("qty > 5 ? \"多\" : \"少\"", lookup)
+                .unwrap()
+                .to_string(),
+            "少"
+        );
+        assert_eq!(
+            evaluate("\"共\" + qty + \"件\"", lookup)
+                .unwrap()
+                .to_string(),
+            "共3件"
+        );
+        assert!(evaluate("photo1", lookup).is_err());
+
+        // 前导0和18位编号原样传给过滤器，拼接时也保留原始字符串
+        let mut docx_template = DocxTemplate::new();
+        docx_template.add_text_replacement("{{code}}", "007");
+        docx_template.add_text_replacement("{{id}}", "110101199003071234");
+        docx_template.add_text_replacement("{{note}}", "{{code + 1}}");
+        let template = crate::docx::CompiledTemplate::from_bytes(&minimal_docx(
+            r#"<w:p><w:r><w:t>{{code|fullwidth_digits}}</w:t></w:r></w:p><w:p><w:r><w:t>{{id|fullwidth_digits}}</w:t></w:r></w:p><w:p><w:r><w:t>{{"No." + code}}</w:t></w:r></w:p><w:p><w:r><w:t>{{note}}</w:t></w:r></w:p>"#,
+        ))
+        .unwrap();
+        let document = read_part(
+            &template.render(&docx_template).unwrap(),
+            "word/document.xml",
+        );
+        assert!(document.contains("<w:t>００７</w:t>"));
+        assert!(document.contains("<w:t>１１０１０１１９９００３０７１２３４</w:t>"));
+        assert!(document.contains("<w:t>No.007</w:t>"));
+        // 数据中的占位符原样写入，不作为表达式计算
+        assert!(document.contains("<w:t>{{code + 1}}</w:t>"));
+    }
 }
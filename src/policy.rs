@@ -0,0 +1,260 @@
+use crate::error::DocxError;
+use reqwest::Url;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+/// 在线图片地址的访问策略，防止通过用户提供的url访问内网服务
+#[derive(Debug, Clone)]
+pub struct UrlPolicy {
+    // 允许的协议
+    schemes: Vec<String>,
+    // 是否允许内网、回环、链路本地等地址
+    allow_private: bool,
+    // 最大重定向次数
+    max_redirects: usize,
+    // 允许的域名，为空时不限制，支持 *.example.com
+    allowed_hosts: Vec<String>,
+    // 禁止的域名，支持 *.example.com
+    denied_hosts: Vec<String>,
+}
+
+impl Default for UrlPolicy {
+    fn default() -> Self {
+        UrlPolicy {
+            schemes: vec!["http".to_string(), "https".to_string()],
+            allow_private: false,
+            max_redirects: 5,
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+        }
+    }
+}
+
+impl UrlPolicy {
+    /// 创建访问策略：只允许http(s)，禁止内网地址，最多重定向5次
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置允许的协议
+    /// @param schemes 协议列表，例如 ["https"]
+    pub fn schemes(mut self, schemes: &[&str]) -> Self {
+        self.schemes = schemes.iter().map(|s| s.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// 设置是否允许访问内网、回环、链路本地等地址
+    /// @param allow_private 是否允许
+    pub fn allow_private(mut self, allow_private: bool) -> Self {
+        self.allow_private = allow_private;
+        self
+    }
+
+    /// 设置最大重定向次数
+    /// @param max_redirects 重定向次数，0表示不允许重定向
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// 添加允许的域名，添加后只能访问允许的域名
+    /// @param host 域名，例如 img.example.com 或 *.example.com
+    pub fn allow_host(mut self, host: &str) -> Self {
+        self.allowed_hosts.push(host.to_ascii_lowercase());
+        self
+    }
+
+    /// 添加禁止的域名
+    /// @param host 域名，例如 img.example.com 或 *.example.com
+    pub fn deny_host(mut self, host: &str) -> Self {
+        self.denied_hosts.push(host.to_ascii_lowercase());
+        self
+    }
+
+    /// 检查url是否允许访问，域名解析后的地址在请求时检查
+    /// @param url 图片url路径
+    pub fn check_url(&self, url: &str) -> Result<(), DocxError> {
+        let parsed = Url::parse(url).map_err(|e| not_allowed(url, &e.to_string()))?;
+        self.check_parsed_url(&parsed)
+            .map_err(|violation| not_allowed(url, &violation.0))
+    }
+
+    /// 检查解析后的url
+    fn check_parsed_url(&self, url: &Url) -> Result<(), PolicyViolation> {
+        if !self.schemes.iter().any(|s| s == url.scheme()) {
+            return Err(PolicyViolation(format!("scheme {}", url.scheme())));
+        }
+        let host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return Err(PolicyViolation("missing host".to_string())),
+        };
+        // ip地址不会经过域名解析，直接检查
+        if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() {
+            self.check_ip(ip)?;
+        }
+        if self.denied_hosts.iter().any(|h| host_matches(h, &host)) {
+            return Err(PolicyViolation(format!("host {} is denied", host)));
+        }
+        if !self.allowed_hosts.is_empty()
+            && !self.allowed_hosts.iter().any(|h| host_matches(h, &host))
+        {
+            return Err(PolicyViolation(format!("host {} is not allowed", host)));
+        }
+        Ok(())
+    }
+
+    /// 检查ip地址是否允许访问
+    fn check_ip(&self, ip: IpAddr) -> Result<(), PolicyViolation> {
+        if !self.allow_private && is_private_ip(ip) {
+            return Err(PolicyViolation(format!("address {} is private", ip)));
+        }
+        Ok(())
+    }
+
+    /// 创建重定向策略，检查次数和每次重定向的地址
+    pub(crate) fn redirect_policy(self: &Arc<Self>) -> redirect::Policy {
+        let policy = self.clone();
+        redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > policy.max_redirects {
+                let violation =
+                    PolicyViolation(format!("more than {} redirects", policy.max_redirects));
+                return attempt.error(violation);
+            }
+            match policy.check_parsed_url(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(violation) => attempt.error(violation),
+            }
+        })
+    }
+
+    /// 创建域名解析对象，解析后检查ip地址
+    pub(crate) fn resolver(self: &Arc<Self>) -> Arc<PolicyResolver> {
+        Arc::new(PolicyResolver(self.clone()))
+    }
+}
+
+/// 请求错误中包含访问策略错误时转换为UrlNotAllowed
+/// @param error 请求错误
+/// @param url 图片url路径
+pub(crate) fn map_policy_error(error: reqwest::Error, url: &str) -> DocxError {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&error);
+    while let Some(e) = source {
+        if let Some(violation) = e.downcast_ref::<PolicyViolation>() {
+            return not_allowed(url, &violation.0);
+        }
+        source = e.source();
+    }
+    DocxError::InvalidImageUrl(error)
+}
+
+fn not_allowed(url: &str, reason: &str) -> DocxError {
+    DocxError::UrlNotAllowed(format!("{} ({})", url, reason))
+}
+
+// 访问策略错误，用于在请求错误中传递
+#[derive(Debug)]
+struct PolicyViolation(String);
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
+
+// 检查解析结果的域名解析对象
+pub(crate) struct PolicyResolver(Arc<UrlPolicy>);
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.0.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            // 任意一个地址不允许访问时拒绝，防止解析到多个地址绕过检查
+            for addr in &addrs {
+                policy.check_ip(addr.ip())?;
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// 域名匹配，*.example.com 匹配所有子域名
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+/// 判断是否为内网、回环、链路本地等不应从外部访问的地址
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ipv4(ip),
+            None => is_private_ipv6(ip),
+        },
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // 组播地址 224.0.0.0/4
+        || ip.is_multicast()
+        // 0.0.0.0/8
+        || a == 0
+        // 运营商级NAT 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // IETF协议分配地址 192.0.0.0/24
+        || (a == 192 && b == 0 && c == 0)
+        // 网络性能测试地址 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // 保留地址 240.0.0.0/4
+        || a >= 240
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    if let Some(ip) = embedded_ipv4(ip) {
+        return is_private_ipv4(ip);
+    }
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // 唯一本地地址 fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // 链路本地地址 fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // 已废弃的站点本地地址 fec0::/10
+        || (first & 0xffc0) == 0xfec0
+        // 组播地址 ff00::/8
+        || ip.is_multicast()
+}
+
+/// 提取IPv6地址中嵌入的IPv4地址，包括兼容地址 ::a.b.c.d、NAT64 64:ff9b::/96 和 6to4 2002::/16
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let [.., a, b, c, d] = ip.octets();
+    match segments {
+        // ::和::1按IPv6地址判断
+        [0, 0, 0, 0, 0, 0, ..] if !ip.is_loopback() && !ip.is_unspecified() => {
+            Some(Ipv4Addr::new(a, b, c, d))
+        }
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(a, b, c, d)),
+        [0x2002, high, low, ..] => Some(Ipv4Addr::from((high as u32) << 16 | low as u32)),
+        _ => None,
+    }
+}
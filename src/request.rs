@@ -1,35 +1,475 @@
 use crate::error::DocxError;
-use crate::image::get_extension;
-use reqwest::Client;
+use crate::fetcher::{FetchFuture, FetchResult, ImageFetcher};
+use crate::image::guess_extension;
+use crate::policy::{UrlPolicy, map_policy_error};
+use log::debug;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Proxy, RequestBuilder};
+use std::sync::Arc;
+use std::time::Duration;
+
+// 默认请求超时时间
+static DEFAULT_TIMEOUT: Duration = Duration::from_secs(100);
+// 默认最大图片大小50MB
+static DEFAULT_MAX_BYTES: u64 = 50 * 1024 * 1024;
 
 /// 获取图片数据   
 /// @param client 请求客户端  
 /// @param url 图片url路径  
 /// @return (data, ext) 返回 Vec<u8>和图片扩展名  
-pub async fn request_image_data(
-    client: &Client,
+pub async fn request_image_data(client: &Client, url: &str) -> FetchResult {
+    send_image_request(client.get(url), url, &FetchLimits::default()).await
+}
+
+/// 发送图片请求并读取图片数据
+/// @param request 请求对象
+/// @param url 图片url路径
+/// @param limits 下载限制
+async fn send_image_request(
+    request: RequestBuilder,
+    url: &str,
+    limits: &FetchLimits,
+) -> FetchResult {
+    // 发送请求并检查状态码
+    let response = request.send().await.map_err(|e| map_policy_error(e, url))?;
+    let mut response = response.error_for_status()?;
+    // 检查图片大小
+    limits.check_length(response.content_length(), url)?;
+    // 分块读取字节，超过大小限制时停止
+    let mut image_data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        image_data.extend_from_slice(&chunk);
+        limits.check_length(Some(image_data.len() as u64), url)?;
+    }
+    let extension = image_content_extension(&image_data, url)?;
+    Ok((image_data, extension))
+}
+
+/// 同步获取图片数据   
+/// @param client 同步请求客户端  
+/// @param url 图片url路径  
+/// @return (data, ext) 返回 Vec<u8>和图片扩展名  
+#[cfg(feature = "blocking")]
+pub fn request_image_data_blocking(client: &reqwest::blocking::Client, url: &str) -> FetchResult {
+    send_image_request_blocking(client.get(url), url, &FetchLimits::default())
+}
+
+/// 同步发送图片请求并读取图片数据
+/// @param request 同步请求对象
+/// @param url 图片url路径
+/// @param limits 下载限制
+#[cfg(feature = "blocking")]
+fn send_image_request_blocking(
+    request: reqwest::blocking::RequestBuilder,
     url: &str,
-) -> Result<(Vec<u8>, String), DocxError> {
-    // 发送请求
-    let response = client.get(url).send().await?;
-    // 检查状态码
-    if !response.status().is_success() {
-        return Err(DocxError::NotImage("请求图片错误".to_string()));
-    }
-    // 获取请求头
-    let headers = response.headers().clone();
-    // 先读取头信息
-    let content_type = headers
-        .get(reqwest::header::CONTENT_TYPE)
-        .map(|h| h.to_str().unwrap_or(""))
-        .unwrap_or("");
-    // 获取内容类型以验证是否为图片
-    let extension = if !content_type.starts_with("image/") {
-        get_extension(url)?
-    } else {
-        &content_type[6..]
+    limits: &FetchLimits,
+) -> FetchResult {
+    use std::io::Read;
+
+    // 发送请求并检查状态码
+    let response = request.send().map_err(|e| map_policy_error(e, url))?;
+    let response = response.error_for_status()?;
+    // 检查图片大小
+    limits.check_length(response.content_length(), url)?;
+    // 读取字节，最多读取到超过大小限制的1个字节
+    let mut image_data = Vec::new();
+    match limits.max_bytes {
+        Some(max_bytes) => response.take(max_bytes + 1).read_to_end(&mut image_data)?,
+        None => response.take(u64::MAX).read_to_end(&mut image_data)?,
     };
-    // 读取字节
-    let image_data = response.bytes().await?.to_vec();
-    Ok((image_data, extension.to_string()))
+    limits.check_length(Some(image_data.len() as u64), url)?;
+    let extension = image_content_extension(&image_data, url)?;
+    Ok((image_data, extension))
+}
+
+/// 根据图片内容判断扩展名，不使用url和响应头中可能错误的类型，
+/// 同时防止把错误页面等内容当作图片写入文档
+/// @param image_data 图片数据
+/// @param url 图片url路径
+fn image_content_extension(image_data: &[u8], url: &str) -> Result<String, DocxError> {
+    guess_extension(image_data)
+        .map(|ext| ext.to_string())
+        .map_err(|_| DocxError::NotImage(url.to_string()))
+}
+
+/// 判断错误是否可以重试：超时、连接失败、读取响应内容失败以及服务端5xx错误
+/// @param error 错误信息
+fn is_retryable(error: &DocxError) -> bool {
+    match error {
+        DocxError::InvalidImageUrl(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.is_body()
+                || e.status().is_some_and(|status| status.is_server_error())
+        }
+        // 同步请求读取响应内容失败
+        DocxError::Io(_) => true,
+        _ => false,
+    }
+}
+
+/// 图片下载限制和重试策略
+#[derive(Debug, Clone)]
+pub struct FetchLimits {
+    // 最大图片大小（字节），None表示不限制
+    pub max_bytes: Option<u64>,
+    // 失败后的重试次数
+    pub retries: u32,
+    // 第一次重试前的等待时间，之后每次翻倍
+    pub backoff: Duration,
+}
+
+impl Default for FetchLimits {
+    fn default() -> Self {
+        FetchLimits {
+            max_bytes: Some(DEFAULT_MAX_BYTES),
+            retries: 0,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl FetchLimits {
+    /// 检查图片大小是否超过限制
+    fn check_length(&self, length: Option<u64>, url: &str) -> Result<(), DocxError> {
+        match (length, self.max_bytes) {
+            (Some(length), Some(max_bytes)) if length > max_bytes => Err(DocxError::ImageTooLarge(
+                format!("{} ({} > {} bytes)", url, length, max_bytes),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// 第几次重试前的等待时间
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+// 请求认证方式
+#[derive(Debug, Clone)]
+enum HttpAuth {
+    Basic(String, Option<String>),
+    Bearer(String),
+}
+
+/// 默认的在线图片获取实现，基于reqwest
+#[derive(Debug, Clone)]
+pub struct HttpImageFetcher {
+    // 请求对象
+    client: Client,
+    // 认证方式
+    auth: Option<HttpAuth>,
+    // 下载限制和重试策略
+    limits: FetchLimits,
+    // 地址访问策略
+    policy: Option<Arc<UrlPolicy>>,
+}
+
+impl HttpImageFetcher {
+    /// 使用默认配置创建，超时时间100秒
+    pub fn new() -> Result<Self, DocxError> {
+        Self::builder().build()
+    }
+
+    /// 创建配置对象
+    pub fn builder() -> HttpImageFetcherBuilder {
+        HttpImageFetcherBuilder::default()
+    }
+
+    /// 使用已有的请求对象创建
+    /// @param client 请求客户端
+    pub fn from_client(client: Client) -> Self {
+        HttpImageFetcher {
+            client,
+            auth: None,
+            limits: FetchLimits::default(),
+            policy: None,
+        }
+    }
+}
+
+impl HttpImageFetcher {
+    /// 创建图片请求
+    fn request(&self, url: &str) -> RequestBuilder {
+        match &self.auth {
+            Some(HttpAuth::Basic(user, password)) => {
+                self.client.get(url).basic_auth(user, password.as_ref())
+            }
+            Some(HttpAuth::Bearer(token)) => self.client.get(url).bearer_auth(token),
+            None => self.client.get(url),
+        }
+    }
+}
+
+impl ImageFetcher for HttpImageFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+        Box::pin(async move {
+            if let Some(policy) = &self.policy {
+                policy.check_url(url)?;
+            }
+            let mut attempt = 0;
+            loop {
+                match send_image_request(self.request(url), url, &self.limits).await {
+                    Err(e) if attempt < self.limits.retries && is_retryable(&e) => {
+                        debug!("retry image {} after error: {}", url, e);
+                        tokio::time::sleep(self.limits.backoff(attempt)).await;
+                        attempt += 1;
+                    }
+                    result => return result,
+                }
+            }
+        })
+    }
+}
+
+/// 同步的在线图片获取实现，基于reqwest
+#[cfg(feature = "blocking")]
+#[derive(Debug, Clone)]
+pub struct BlockingHttpImageFetcher {
+    // 同步请求对象
+    client: reqwest::blocking::Client,
+    // 认证方式
+    auth: Option<HttpAuth>,
+    // 下载限制和重试策略
+    limits: FetchLimits,
+    // 地址访问策略
+    policy: Option<Arc<UrlPolicy>>,
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingHttpImageFetcher {
+    /// 使用默认配置创建，超时时间100秒
+    pub fn new() -> Result<Self, DocxError> {
+        HttpImageFetcher::builder().build_blocking()
+    }
+
+    /// 使用已有的同步请求对象创建
+    /// @param client 同步请求客户端
+    pub fn from_client(client: reqwest::blocking::Client) -> Self {
+        BlockingHttpImageFetcher {
+            client,
+            auth: None,
+            limits: FetchLimits::default(),
+            policy: None,
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingHttpImageFetcher {
+    /// 创建图片请求
+    fn request(&self, url: &str) -> reqwest::blocking::RequestBuilder {
+        match &self.auth {
+            Some(HttpAuth::Basic(user, password)) => {
+                self.client.get(url).basic_auth(user, password.as_ref())
+            }
+            Some(HttpAuth::Bearer(token)) => self.client.get(url).bearer_auth(token),
+            None => self.client.get(url),
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::fetcher::BlockingImageFetcher for BlockingHttpImageFetcher {
+    fn fetch(&self, url: &str) -> FetchResult {
+        if let Some(policy) = &self.policy {
+            policy.check_url(url)?;
+        }
+        let mut attempt = 0;
+        loop {
+            match send_image_request_blocking(self.request(url), url, &self.limits) {
+                Err(e) if attempt < self.limits.retries && is_retryable(&e) => {
+                    debug!("retry image {} after error: {}", url, e);
+                    std::thread::sleep(self.limits.backoff(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// 在线图片获取配置
+#[derive(Debug, Clone)]
+pub struct HttpImageFetcherBuilder {
+    // 请求超时时间
+    timeout: Duration,
+    // 连接超时时间
+    connect_timeout: Option<Duration>,
+    // 代理地址
+    proxy: Option<String>,
+    // 请求头
+    headers: Vec<(String, String)>,
+    // 用户代理
+    user_agent: Option<String>,
+    // 认证方式
+    auth: Option<HttpAuth>,
+    // 下载限制和重试策略
+    limits: FetchLimits,
+    // 地址访问策略
+    policy: Option<Arc<UrlPolicy>>,
+}
+
+impl Default for HttpImageFetcherBuilder {
+    fn default() -> Self {
+        HttpImageFetcherBuilder {
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: None,
+            proxy: None,
+            headers: Vec::new(),
+            user_agent: None,
+            auth: None,
+            limits: FetchLimits::default(),
+            policy: None,
+        }
+    }
+}
+
+impl HttpImageFetcherBuilder {
+    /// 设置请求超时时间
+    /// @param timeout 超时时间
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 设置连接超时时间
+    /// @param timeout 超时时间
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// 设置代理，例如 http://127.0.0.1:8080
+    /// @param proxy 代理地址
+    pub fn proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// 添加请求头
+    /// @param name 请求头名称
+    /// @param value 请求头内容
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// 设置用户代理
+    /// @param user_agent 用户代理
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// 设置Basic认证
+    /// @param user 用户名
+    /// @param password 密码
+    pub fn basic_auth(mut self, user: &str, password: Option<&str>) -> Self {
+        self.auth = Some(HttpAuth::Basic(
+            user.to_string(),
+            password.map(|p| p.to_string()),
+        ));
+        self
+    }
+
+    /// 设置Bearer认证
+    /// @param token 认证令牌
+    pub fn bearer_auth(mut self, token: &str) -> Self {
+        self.auth = Some(HttpAuth::Bearer(token.to_string()));
+        self
+    }
+
+    /// 设置最大图片大小，默认50MB
+    /// @param max_bytes 最大字节数，None表示不限制
+    pub fn max_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.limits.max_bytes = max_bytes;
+        self
+    }
+
+    /// 设置超时、连接失败和5xx错误的重试次数，等待时间每次翻倍
+    /// @param retries 重试次数
+    /// @param backoff 第一次重试前的等待时间
+    pub fn retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.limits.retries = retries;
+        self.limits.backoff = backoff;
+        self
+    }
+
+    /// 设置地址访问策略，用于处理用户提供的url，防止访问内网服务。
+    /// 使用代理时域名由代理解析，只能检查url中的ip地址
+    /// @param policy 访问策略
+    pub fn url_policy(mut self, policy: UrlPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// 创建异步的在线图片获取对象
+    pub fn build(self) -> Result<HttpImageFetcher, DocxError> {
+        let mut builder = Client::builder()
+            .timeout(self.timeout)
+            .default_headers(self.header_map()?);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(policy) = &self.policy {
+            builder = builder
+                .redirect(policy.redirect_policy())
+                .dns_resolver(policy.resolver());
+        }
+        Ok(HttpImageFetcher {
+            client: builder.build()?,
+            auth: self.auth,
+            limits: self.limits,
+            policy: self.policy,
+        })
+    }
+
+    /// 创建同步的在线图片获取对象
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<BlockingHttpImageFetcher, DocxError> {
+        let mut builder = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .default_headers(self.header_map()?);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(policy) = &self.policy {
+            builder = builder
+                .redirect(policy.redirect_policy())
+                .dns_resolver(policy.resolver());
+        }
+        Ok(BlockingHttpImageFetcher {
+            client: builder.build()?,
+            auth: self.auth,
+            limits: self.limits,
+            policy: self.policy,
+        })
+    }
+
+    /// 转换请求头
+    fn header_map(&self) -> Result<HeaderMap, DocxError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| DocxError::FetcherConfig(format!("{}: {}", name, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| DocxError::FetcherConfig(format!("{}: {}", value, e)))?;
+            headers.append(name, value);
+        }
+        Ok(headers)
+    }
 }
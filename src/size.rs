@@ -0,0 +1,96 @@
+use crate::error::DocxError;
+use crate::image::{CROP_UNIT, DocxImage};
+use image::ImageReader;
+use std::io::Cursor;
+
+/// 长度，支持厘米、毫米、英寸、磅和像素（96DPI）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Cm(f32),
+    Mm(f32),
+    Inch(f32),
+    Pt(f32),
+    Px(f32),
+}
+
+impl Length {
+    /// 换算成emu
+    pub fn to_emu(self) -> u64 {
+        let emu = match self {
+            Length::Cm(v) => v as f64 * 360000.0,
+            Length::Mm(v) => v as f64 * 36000.0,
+            Length::Inch(v) => v as f64 * 914400.0,
+            Length::Pt(v) => v as f64 * 12700.0,
+            Length::Px(v) => v as f64 * 9525.0,
+        };
+        emu.max(0.0) as u64
+    }
+}
+
+/// 图片大小的适应方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageSize {
+    // 固定宽度和高度，图片可能变形
+    Stretch(Length, Length),
+    // 在宽高范围内等比例缩放，完整显示图片
+    Contain(Length, Length),
+    // 等比例缩放填满宽高范围，超出部分裁剪
+    Cover(Length, Length),
+    // 固定宽度，高度按比例计算
+    Width(Length),
+    // 固定高度，宽度按比例计算
+    Height(Length),
+}
+
+impl DocxImage {
+    /// 按适应方式设置图片大小
+    /// @param size 图片大小的适应方式
+    pub fn fit(&mut self, size: ImageSize) -> Result<(), DocxError> {
+        let (width_px, height_px) = ImageReader::new(Cursor::new(&self.image_data))
+            .with_guessed_format()?
+            .into_dimensions()?;
+        if width_px == 0 || height_px == 0 {
+            return Err(DocxError::NotImage(self.image_path.clone()));
+        }
+        // 图片宽高比
+        let ratio = width_px as f64 / height_px as f64;
+        self.crop = None;
+        self.auto_size = false;
+        (self.width, self.height) = match size {
+            ImageSize::Stretch(width, height) => (width.to_emu(), height.to_emu()),
+            ImageSize::Width(width) => {
+                let width = width.to_emu();
+                (width, (width as f64 / ratio) as u64)
+            }
+            ImageSize::Height(height) => {
+                let height = height.to_emu();
+                ((height as f64 * ratio) as u64, height)
+            }
+            ImageSize::Contain(width, height) => {
+                let (width, height) = (width.to_emu(), height.to_emu());
+                if width as f64 / height.max(1) as f64 > ratio {
+                    ((height as f64 * ratio) as u64, height)
+                } else {
+                    (width, (width as f64 / ratio) as u64)
+                }
+            }
+            ImageSize::Cover(width, height) => {
+                let (width, height) = (width.to_emu(), height.to_emu());
+                let box_ratio = width as f64 / height.max(1) as f64;
+                // 图片比范围宽时裁剪左右，否则裁剪上下
+                let crop = if ratio > box_ratio {
+                    let side = ((1.0 - box_ratio / ratio) / 2.0 * CROP_UNIT) as u32;
+                    [side, 0, side, 0]
+                } else {
+                    let side = ((1.0 - ratio / box_ratio) / 2.0 * CROP_UNIT) as u32;
+                    [0, side, 0, side]
+                };
+                if crop != [0; 4] {
+                    self.crop = Some(crop);
+                }
+                (width, height)
+            }
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error::DocxError;
+use crate::size::Length;
+
+// 比例的单位，100000表示100%
+static PERCENT_UNIT: f32 = 100000.0;
+// 角度的单位，60000表示1度
+static ANGLE_UNIT: f64 = 60000.0;
+
+/// 图片样式：旋转、翻转、裁剪、边框和阴影
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageStyle {
+    // 顺时针旋转角度（度）
+    rotation: f32,
+    // 水平翻转
+    flip_horizontal: bool,
+    // 垂直翻转
+    flip_vertical: bool,
+    // 裁剪比例（百分比），左、上、右、下
+    crop: Option<[f32; 4]>,
+    // 边框
+    outline: Option<Outline>,
+    // 阴影
+    shadow: Option<Shadow>,
+}
+
+/// 图片边框
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outline {
+    // 颜色，例如FF0000
+    pub color: String,
+    // 宽度
+    pub width: Length,
+}
+
+/// 图片外部阴影
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadow {
+    // 颜色，例如000000
+    pub color: String,
+    // 透明度（0-1），1表示不透明
+    pub alpha: f32,
+    // 模糊半径
+    pub blur: Length,
+    // 阴影距离
+    pub distance: Length,
+    // 阴影方向（度），0表示向右，90表示向下
+    pub direction: f32,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Shadow {
+            color: "000000".to_string(),
+            alpha: 0.4,
+            blur: Length::Pt(4.0),
+            distance: Length::Pt(3.0),
+            direction: 45.0,
+        }
+    }
+}
+
+impl ImageStyle {
+    /// 创建图片样式
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 顺时针旋转图片
+    /// @param degrees 旋转角度（度）
+    pub fn rotate(mut self, degrees: f32) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    /// 水平翻转图片
+    pub fn flip_horizontal(mut self) -> Self {
+        self.flip_horizontal = !self.flip_horizontal;
+        self
+    }
+
+    /// 垂直翻转图片
+    pub fn flip_vertical(mut self) -> Self {
+        self.flip_vertical = !self.flip_vertical;
+        self
+    }
+
+    /// 裁剪图片，图片大小为裁剪后的显示大小
+    /// @param left 左侧裁剪的百分比
+    /// @param top 上侧裁剪的百分比
+    /// @param right 右侧裁剪的百分比
+    /// @param bottom 下侧裁剪的百分比
+    pub fn crop(mut self, left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        self.crop = Some([left, top, right, bottom]);
+        self
+    }
+
+    /// 设置边框
+    /// @param color 颜色，例如FF0000
+    /// @param width 宽度
+    pub fn outline(mut self, color: &str, width: Length) -> Self {
+        self.outline = Some(Outline {
+            color: color.to_string(),
+            width,
+        });
+        self
+    }
+
+    /// 设置阴影
+    /// @param shadow 阴影
+    pub fn shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    /// 检查样式是否有效
+    pub fn validate(&self) -> Result<(), DocxError> {
+        let colors = self.outline.iter().map(|o| &o.color);
+        for color in colors.chain(self.shadow.iter().map(|s| &s.color)) {
+            if color.len() != 6 || !color.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(DocxError::Format(format!("invalid color {}", color)));
+            }
+        }
+        if let Some(crop) = self.crop {
+            let valid = crop.iter().all(|v| (0.0..100.0).contains(v))
+                && crop[0] + crop[2] < 100.0
+                && crop[1] + crop[3] < 100.0;
+            if !valid {
+                return Err(DocxError::Format(format!("invalid crop {:?}", crop)));
+            }
+        }
+        Ok(())
+    }
+
+    /// 裁剪比例（1/100000），左、上、右、下
+    pub(crate) fn crop_rect(&self) -> Option<[u32; 4]> {
+        self.crop
+            .map(|crop| crop.map(|v| (v / 100.0 * PERCENT_UNIT) as u32))
+    }
+
+    /// a:xfrm标签的旋转和翻转属性
+    pub(crate) fn xfrm_attributes(&self) -> String {
+        let mut attributes = String::new();
+        let rotation = (self.rotation as f64).rem_euclid(360.0);
+        if rotation != 0.0 {
+            attributes.push_str(&format!(r#" rot="{}""#, (rotation * ANGLE_UNIT) as u64));
+        }
+        if self.flip_horizontal {
+            attributes.push_str(r#" flipH="1""#);
+        }
+        if self.flip_vertical {
+            attributes.push_str(r#" flipV="1""#);
+        }
+        attributes
+    }
+
+    /// 边框和阴影标签
+    pub(crate) fn effect_elements(&self) -> String {
+        let mut elements = String::new();
+        if let Some(outline) = &self.outline {
+            elements.push_str(&format!(
+                r#"<a:ln w="{}"><a:solidFill><a:srgbClr val="{}"/></a:solidFill></a:ln>"#,
+                outline.width.to_emu(),
+                outline.color
+            ));
+        }
+        if let Some(shadow) = &self.shadow {
+            let direction = (shadow.direction as f64).rem_euclid(360.0);
+            elements.push_str(&format!(
+                r#"<a:effectLst><a:outerShdw blurRad="{}" dist="{}" dir="{}" algn="ctr" rotWithShape="0"><a:srgbClr val="{}"><a:alpha val="{}"/></a:srgbClr></a:outerShdw></a:effectLst>"#,
+                shadow.blur.to_emu(),
+                shadow.distance.to_emu(),
+                (direction * ANGLE_UNIT) as u64,
+                shadow.color,
+                (shadow.alpha.clamp(0.0, 1.0) * PERCENT_UNIT) as u32
+            ));
+        }
+        elements
+    }
+
+    /// 旋转和边框超出图片大小的部分，嵌入文字中的图片需要预留位置
+    /// @param width 图片宽度（emu）
+    /// @param height 图片高度（emu）
+    /// @return (x, y) 左右和上下各超出的大小（emu）
+    pub(crate) fn effect_extent(&self, width: u64, height: u64) -> (u64, u64) {
+        let (width, height) = (width as f64, height as f64);
+        let radians = (self.rotation as f64).to_radians();
+        let (sin, cos) = (radians.sin().abs(), radians.cos().abs());
+        // 旋转后的外接矩形
+        let bound_width = width * cos + height * sin;
+        let bound_height = width * sin + height * cos;
+        let outline = self
+            .outline
+            .as_ref()
+            .map_or(0.0, |o| o.width.to_emu() as f64 / 2.0);
+        let x = ((bound_width - width) / 2.0).max(0.0) + outline;
+        let y = ((bound_height - height) / 2.0).max(0.0) + outline;
+        (x.round() as u64, y.round() as u64)
+    }
+}
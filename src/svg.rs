@@ -0,0 +1,60 @@
+use crate::error::DocxError;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{Options, Tree, fontdb};
+use std::sync::{Arc, OnceLock};
+
+// 备用PNG图片相对SVG原始大小的放大倍数，避免高分辨率屏幕上模糊
+static FALLBACK_SCALE: f32 = 2.0;
+// 备用PNG图片的最大边长（像素）
+static MAX_FALLBACK_PX: f32 = 4096.0;
+
+/// 系统字体，首次渲染带文字的SVG时加载
+fn system_fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut database = fontdb::Database::new();
+            database.load_system_fonts();
+            Arc::new(database)
+        })
+        .clone()
+}
+
+/// 解析SVG图片
+/// @param svg_data SVG图片数据
+fn parse_svg(svg_data: &[u8]) -> Result<Tree, DocxError> {
+    let options = Options {
+        fontdb: system_fonts(),
+        ..Options::default()
+    };
+    Ok(Tree::from_data(svg_data, &options)?)
+}
+
+/// 获取SVG图片的大小
+/// @param svg_data SVG图片数据
+/// @return (width, height) 图片大小（像素，96DPI）
+pub fn get_svg_size(svg_data: &[u8]) -> Result<(f32, f32), DocxError> {
+    let size = parse_svg(svg_data)?.size();
+    Ok((size.width(), size.height()))
+}
+
+/// 将SVG图片转换为PNG，作为不支持SVG的Word版本显示的备用图片
+/// @param svg_data SVG图片数据
+/// @return PNG图片数据
+pub fn rasterize_svg(svg_data: &[u8]) -> Result<Vec<u8>, DocxError> {
+    let tree = parse_svg(svg_data)?;
+    let size = tree.size();
+    let scale = FALLBACK_SCALE.min(MAX_FALLBACK_PX / size.width().max(size.height()));
+    let width = (size.width() * scale).ceil() as u32;
+    let height = (size.height() * scale).ceil() as u32;
+    let mut pixmap = Pixmap::new(width.max(1), height.max(1))
+        .ok_or_else(|| DocxError::NotImage("invalid svg size".to_string()))?;
+    resvg::render(
+        &tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    pixmap
+        .encode_png()
+        .map_err(|e| DocxError::NotImage(e.to_string()))
+}
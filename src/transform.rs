@@ -0,0 +1,183 @@
+use crate::error::DocxError;
+use crate::image::{DocxImage, EMU, get_image_orientation};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use log::warn;
+use std::io::Cursor;
+
+// 转换为JPEG时的默认质量
+static DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// 图片写入文档前的处理选项
+#[derive(Debug, Clone)]
+pub struct ImageOptions {
+    // 是否将Word不支持的图片格式（WebP、AVIF、TIFF等）转换为PNG或JPEG
+    pub convert_unsupported: bool,
+    // 按显示大小缩小图片的目标DPI，例如150、220，None表示不缩小
+    pub target_dpi: Option<u32>,
+    // JPEG图片重新压缩的质量（1-100），None表示不重新压缩
+    pub jpeg_quality: Option<u8>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        ImageOptions {
+            convert_unsupported: true,
+            target_dpi: None,
+            jpeg_quality: None,
+        }
+    }
+}
+
+impl DocxImage {
+    /// 按处理选项处理图片，处理失败时保留原图片
+    /// 按目标DPI缩小在写入文档时进行，此时图片的实际显示大小已经确定
+    /// @param options 处理选项
+    pub fn apply_options(self, options: &ImageOptions) -> DocxImage {
+        let mut docx_image = match self.transform(options) {
+            Ok(Some(docx_image)) => docx_image,
+            Ok(None) => self,
+            Err(e) => {
+                warn!("{} 图片处理失败，使用原图片: {}", self.image_path, e);
+                self
+            }
+        };
+        docx_image.image_options = options.clone();
+        docx_image
+    }
+
+    /// 按实际显示大小和目标DPI缩小图片，保持图片格式不变，不需要缩小或处理失败时返回None
+    /// @param width_emu 图片在文档中的最大显示宽度（emu）
+    /// @param height_emu 图片在文档中的最大显示高度（emu）
+    pub fn display_data(&self, width_emu: u64, height_emu: u64) -> Option<Vec<u8>> {
+        let dpi = self.image_options.target_dpi?;
+        let format = image::guess_format(&self.image_data).ok()?;
+        // 动图缩小后会丢失动画，不处理
+        if format == ImageFormat::Gif {
+            return None;
+        }
+        let result = self
+            .target_size(dpi, width_emu, height_emu)
+            .and_then(|target_size| {
+                let Some((width, height)) = target_size else {
+                    return Ok(None);
+                };
+                let img = image::load_from_memory_with_format(&self.image_data, format)?
+                    .resize_exact(width, height, FilterType::Lanczos3);
+                let quality = self
+                    .image_options
+                    .jpeg_quality
+                    .unwrap_or(DEFAULT_JPEG_QUALITY);
+                encode_image(&img, format, quality).map(Some)
+            });
+        match result {
+            Ok(image_data) => image_data,
+            Err(e) => {
+                warn!("{} 图片缩小失败，使用原图片: {}", self.image_path, e);
+                None
+            }
+        }
+    }
+
+    /// 处理图片，不需要处理时返回None
+    fn transform(&self, options: &ImageOptions) -> Result<Option<DocxImage>, DocxError> {
+        let Ok(format) = image::guess_format(&self.image_data) else {
+            return Ok(None);
+        };
+        let convert = options.convert_unsupported && !is_word_supported(format);
+        // Word不处理EXIF方向，需要按方向旋转图片
+        let orientation = get_image_orientation(&self.image_data);
+        let rotate = orientation != Orientation::NoTransforms;
+        let recompress = options.jpeg_quality.is_some() && format == ImageFormat::Jpeg;
+        if !convert && !rotate && !recompress {
+            return Ok(None);
+        }
+
+        let mut img = image::load_from_memory_with_format(&self.image_data, format)?;
+        img.apply_orientation(orientation);
+        let target = match format {
+            ImageFormat::Jpeg => ImageFormat::Jpeg,
+            // 有透明通道或无损格式转换为PNG，否则转换为JPEG
+            _ if img.color().has_alpha() || !is_lossy(format) => ImageFormat::Png,
+            _ => ImageFormat::Jpeg,
+        };
+        let quality = options.jpeg_quality.unwrap_or(DEFAULT_JPEG_QUALITY);
+        let image_data = encode_image(&img, target, quality)?;
+        // 只重新压缩时，结果更大则保留原图片
+        if !convert && !rotate && image_data.len() >= self.image_data.len() {
+            return Ok(None);
+        }
+        Ok(Some(DocxImage {
+            image_ext: target.extensions_str()[0].to_string(),
+            content_type: target.to_mime_type().to_string(),
+            image_data,
+            ..self.clone()
+        }))
+    }
+
+    /// 计算按显示大小和目标DPI缩小后的像素大小，不需要缩小时返回None
+    /// @param dpi 目标DPI
+    /// @param width_emu 显示宽度（emu）
+    /// @param height_emu 显示高度（emu）
+    fn target_size(
+        &self,
+        dpi: u32,
+        width_emu: u64,
+        height_emu: u64,
+    ) -> Result<Option<(u32, u32)>, DocxError> {
+        let (width_px, height_px) = ImageReader::new(Cursor::new(&self.image_data))
+            .with_guessed_format()?
+            .into_dimensions()?;
+        // 显示大小对应的像素数量，裁剪的图片按整张图片的显示大小计算
+        let (visible_width, visible_height) = self.visible_fraction();
+        let display_width = width_emu as f64 * dpi as f64 / EMU / visible_width;
+        let display_height = height_emu as f64 * dpi as f64 / EMU / visible_height;
+        // 保持宽高比，两个方向都不小于显示需要的像素
+        let scale = (display_width / width_px as f64).max(display_height / height_px as f64);
+        if scale >= 1.0 || width_px == 0 || height_px == 0 {
+            return Ok(None);
+        }
+        let width = ((width_px as f64 * scale).round() as u32).max(1);
+        let height = ((height_px as f64 * scale).round() as u32).max(1);
+        Ok(Some((width, height)))
+    }
+}
+
+/// Word可以正常显示的图片格式
+fn is_word_supported(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::Bmp
+    )
+}
+
+/// 通常为有损压缩的图片格式
+fn is_lossy(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Avif
+    )
+}
+
+/// 编码图片数据
+/// @param img 图片
+/// @param format 图片格式，PNG或JPEG
+/// @param quality JPEG质量（1-100）
+fn encode_image(
+    img: &DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<Vec<u8>, DocxError> {
+    let mut image_data = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Jpeg => {
+            // JPEG不支持透明通道
+            let encoder = JpegEncoder::new_with_quality(&mut image_data, quality);
+            DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder)?;
+        }
+        format => img.write_to(&mut image_data, format)?,
+    }
+    Ok(image_data.into_inner())
+}